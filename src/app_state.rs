@@ -2,11 +2,148 @@ use bevy::prelude::*;
 
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
 pub enum AppState {
+    /// Waiting for the startup assets before showing the menu.
     #[default]
+    Loading,
     MainMenu,
     InGame,
     Paused,
+    GameOver,
 }
 
+impl AppState {
+    /// The state this one is shown on top of. Entities of the underlying state
+    /// are kept alive while the overlay is up.
+    pub fn overlays(self) -> Option<AppState> {
+        match self {
+            AppState::Paused | AppState::GameOver => Some(AppState::InGame),
+            AppState::Loading | AppState::MainMenu | AppState::InGame => None,
+        }
+    }
 
+    /// The underlying state this overlay can go back to without ending it.
+    /// Leaving a game over for `InGame` is a retry, so only pausing resumes.
+    pub fn resumes_into(self) -> Option<AppState> {
+        match self {
+            AppState::Paused => Some(AppState::InGame),
+            _ => None,
+        }
+    }
+}
+
+/// Despawns the entity, with its children, once `0` is exited for good.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StateScoped(pub AppState);
+
+/// Builds the `OnExit(exited)` system despawning the entities scoped to it.
+/// `State` already holds the state being entered while it runs.
+#[allow(clippy::type_complexity)]
+pub fn despawn_scoped(exited: AppState) -> impl FnMut(Commands, Query<(Entity, &StateScoped)>, Res<State<AppState>>) {
+    move |mut commands: Commands, scoped: Query<(Entity, &StateScoped)>, state: Res<State<AppState>>| {
+        let entered = state.0;
+        // going into an overlay of the exited state keeps its world around
+        let keep_exited = entered.overlays() == Some(exited);
+        // leaving an overlay other than by resuming ends the state beneath it too
+        let underlying = exited.overlays().filter(|_| exited.resumes_into() != Some(entered));
+        for (entity, StateScoped(owner)) in scoped.iter(){
+            if (*owner == exited && !keep_exited) || Some(*owner) == underlying{
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+/// Set while `InGame` is being re-entered from `Paused`, so the systems that
+/// build a new run can tell a resume apart from a fresh start.
+#[derive(Resource, Default, Debug)]
+pub struct Resuming(pub bool);
 
+/// Run condition for `OnEnter(AppState::InGame)` systems that set up a new run.
+pub fn starting_run(resuming: Res<Resuming>) -> bool {
+    !resuming.0
+}
+
+/// Run condition for `OnExit` systems that tear a run down. `State` already
+/// holds the state being entered while `OnExit` runs.
+pub fn ending_run(state: Res<State<AppState>>) -> bool {
+    !matches!(state.0, AppState::InGame | AppState::Paused)
+}
+
+pub fn track_resuming(
+    state: Res<State<AppState>>,
+    mut resuming: ResMut<Resuming>,
+){
+    resuming.0 = state.0 == AppState::InGame;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins).add_state::<AppState>();
+        for state in AppState::variants(){
+            app.add_system(despawn_scoped(state).in_schedule(OnExit(state)));
+        }
+        app.update();
+        app
+    }
+
+    fn scoped(app: &mut App) -> Vec<AppState> {
+        let mut states: Vec<AppState> = app.world.query::<&StateScoped>()
+            .iter(&app.world)
+            .map(|scoped| scoped.0)
+            .collect();
+        states.sort_by_key(|state| *state as usize);
+        states
+    }
+
+    /// Moves to `state`, checks what survived the transition, then spawns the
+    /// entity the state's setup would if it has none yet, like a resume.
+    fn go(app: &mut App, state: AppState, survivors: &[AppState]) {
+        app.world.resource_mut::<NextState<AppState>>().set(state);
+        app.update();
+        assert_eq!(app.world.resource::<State<AppState>>().0, state);
+        assert_eq!(scoped(app), survivors, "entities left after entering {:?}", state);
+        if !survivors.contains(&state){
+            app.world.spawn(StateScoped(state));
+        }
+    }
+
+    #[test]
+    fn every_transition_despawns_what_it_should() {
+        use AppState::*;
+        let mut app = app();
+        app.world.spawn(StateScoped(Loading));
+
+        go(&mut app, MainMenu, &[]);
+        go(&mut app, InGame, &[]);
+        // the world stays under the pause overlay and survives resuming
+        go(&mut app, Paused, &[InGame]);
+        go(&mut app, InGame, &[InGame]);
+        go(&mut app, GameOver, &[InGame]);
+        // retrying ends the old run
+        go(&mut app, InGame, &[]);
+        go(&mut app, Paused, &[InGame]);
+        // quitting from the pause menu ends the run too
+        go(&mut app, MainMenu, &[]);
+        go(&mut app, InGame, &[]);
+        go(&mut app, GameOver, &[InGame]);
+        go(&mut app, MainMenu, &[]);
+        assert_eq!(scoped(&mut app), vec![MainMenu]);
+    }
+
+    #[test]
+    fn children_are_despawned_with_their_scoped_parent() {
+        let mut app = app();
+        app.world.resource_mut::<NextState<AppState>>().set(AppState::MainMenu);
+        app.update();
+        let child = app.world.spawn_empty().id();
+        app.world.spawn(StateScoped(AppState::MainMenu)).push_children(&[child]);
+
+        app.world.resource_mut::<NextState<AppState>>().set(AppState::InGame);
+        app.update();
+        assert!(app.world.get_entity(child).is_none());
+    }
+}
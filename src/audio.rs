@@ -1,8 +1,14 @@
 use bevy::prelude::*;
 
+use crate::app_state::{ending_run, AppState};
+
 #[derive(Resource)]
 pub struct AudioController{
-    pub handles: Vec<NamedAudioHandle>
+    pub handles: Vec<NamedAudioHandle>,
+    /// Name of the looping track playing, if any.
+    pub music: Option<String>,
+    pub music_volume: f32,
+    music_sink: Option<Handle<AudioSink>>,
 }
 
 #[derive(Clone, Debug)]
@@ -11,8 +17,19 @@ pub struct NamedAudioHandle{
     pub handle: Handle<AudioSource>,
 }
 
+/// A sound was registered under a name that is already taken.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateHandle(pub String);
+
+impl std::fmt::Display for DuplicateHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "a sound named \"{}\" already exists", self.0)
+    }
+}
+
 pub trait AddHandle{
-    fn add_handle(&mut self, name: &str, handle: Handle<AudioSource>);
+    /// Registers `handle` as `name`, keeping the first handle on a clash.
+    fn add_handle(&mut self, name: &str, handle: Handle<AudioSource>) -> Result<(), DuplicateHandle>;
 }
 
 pub trait GetHandle{
@@ -20,13 +37,13 @@ pub trait GetHandle{
 }
 
 impl AddHandle for AudioController{
-    fn add_handle(&mut self, name: &str, handle: Handle<AudioSource>){
-        if let Some(_handle) = self.get_handle(name){
-            println!("Handle with name {} already exists", name);
-            return;
+    fn add_handle(&mut self, name: &str, handle: Handle<AudioSource>) -> Result<(), DuplicateHandle>{
+        if self.get_handle(name).is_some(){
+            return Err(DuplicateHandle(name.to_string()));
         }
         let named_audio_handle = NamedAudioHandle{name: name.to_string(), handle};
         self.handles.insert(self.handles.len(), named_audio_handle);
+        Ok(())
     }
 }
 
@@ -42,12 +59,62 @@ impl GetHandle for AudioController{
     }
 }
 
+pub trait PlayMusic{
+    /// Loops the named track, stopping whatever track was playing before.
+    fn play_music(&mut self, name: &str, audio: &Audio, sinks: &Assets<AudioSink>);
+    fn stop_music(&mut self, sinks: &Assets<AudioSink>);
+    /// Changes the playing track's volume without touching `music_volume`.
+    fn set_music_volume(&self, volume: f32, sinks: &Assets<AudioSink>);
+}
+
+impl PlayMusic for AudioController{
+    fn play_music(&mut self, name: &str, audio: &Audio, sinks: &Assets<AudioSink>){
+        if self.music.as_deref() == Some(name){
+            return;
+        }
+        self.stop_music(sinks);
+        let Some(track) = self.get_handle(name) else{
+            return;
+        };
+        let sink = audio.play_with_settings(track.handle, PlaybackSettings::LOOP.with_volume(self.music_volume));
+        // the returned handle is weak, keep a strong one to control the track
+        self.music_sink = Some(sinks.get_handle(sink));
+        self.music = Some(name.to_string());
+    }
+
+    fn stop_music(&mut self, sinks: &Assets<AudioSink>){
+        if let Some(sink) = self.music_sink.take().and_then(|sink| sinks.get(&sink)){
+            sink.stop();
+        }
+        self.music = None;
+    }
+
+    fn set_music_volume(&self, volume: f32, sinks: &Assets<AudioSink>){
+        if let Some(sink) = self.music_sink.as_ref().and_then(|sink| sinks.get(sink)){
+            sink.set_volume(volume);
+        }
+    }
+}
+
+fn stop_music(
+    mut audio_controller: ResMut<AudioController>,
+    sinks: Res<Assets<AudioSink>>,
+){
+    audio_controller.stop_music(&sinks);
+}
+
 pub struct AudioPlugin;
 
 impl Plugin for AudioPlugin{
     fn build(&self, app: &mut App){
         app.insert_resource(AudioController{
-            handles: Vec::<NamedAudioHandle>::new()
-        });
+            handles: Vec::<NamedAudioHandle>::new(),
+            music: None,
+            music_volume: 0.3,
+            music_sink: None,
+        })
+        // pausing keeps the music going, ducked
+        .add_system(stop_music.in_schedule(OnExit(AppState::InGame)).run_if(ending_run))
+        .add_system(stop_music.in_schedule(OnExit(AppState::Paused)).run_if(ending_run));
     }
 }
\ No newline at end of file
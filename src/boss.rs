@@ -0,0 +1,242 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::app_state::AppState;
+use crate::audio::*;
+use crate::bullet::{Bullet, Faction, ProjectilePool};
+use crate::director::SpawnEnemyEvent;
+use crate::enemy::EnemyKind;
+use crate::health::Health;
+use crate::player::PlayerInfo;
+use crate::steering::{apply_steering, Steering};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BossAttack {
+    /// A ring of `bullets` enemy projectiles fired outwards at once.
+    RadialBurst { bullets: u32, speed: f32, damage: i32 },
+    /// Rushes in a straight line at where the player was.
+    Charge { speed: f32, duration: f32 },
+    /// Brings in minions through the usual spawn system.
+    Summon { kind: EnemyKind, count: u32 },
+}
+
+/// One stage of a boss fight. A phase starts once the boss's health falls to
+/// `health_threshold` of its maximum.
+#[derive(Clone, Debug)]
+pub struct BossPhase {
+    pub health_threshold: f32,
+    /// Cycled through in order, one every `attack_interval` seconds.
+    pub attacks: Vec<BossAttack>,
+    pub attack_interval: f32,
+    /// Multiplies the boss's walking speed.
+    pub speed_multiplier: f32,
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct Boss {
+    pub phases: Vec<BossPhase>,
+    pub phase: usize,
+    pub attack_timer: Timer,
+    next_attack: usize,
+    base_speed: Option<f32>,
+    charge: Option<(Vec3, Timer)>,
+}
+
+impl Boss {
+    pub fn new(phases: Vec<BossPhase>) -> Self {
+        let interval = phases.first().map_or(3.0, |phase| phase.attack_interval);
+        Boss {
+            phases,
+            phase: 0,
+            attack_timer: Timer::from_seconds(interval, TimerMode::Repeating),
+            next_attack: 0,
+            base_speed: None,
+            charge: None,
+        }
+    }
+
+    pub fn current_phase(&self) -> Option<&BossPhase> {
+        self.phases.get(self.phase)
+    }
+
+    pub fn is_charging(&self) -> bool {
+        self.charge.is_some()
+    }
+}
+
+impl Default for Boss {
+    fn default() -> Self {
+        Boss::new(vec![
+            BossPhase {
+                health_threshold: 1.0,
+                attacks: vec![
+                    BossAttack::RadialBurst { bullets: 12, speed: 6.0, damage: 1 },
+                    BossAttack::Charge { speed: 8.0, duration: 1.0 },
+                ],
+                attack_interval: 3.0,
+                speed_multiplier: 1.0,
+            },
+            BossPhase {
+                health_threshold: 0.6,
+                attacks: vec![
+                    BossAttack::Summon { kind: EnemyKind::Runner, count: 3 },
+                    BossAttack::RadialBurst { bullets: 16, speed: 7.0, damage: 1 },
+                    BossAttack::Charge { speed: 10.0, duration: 1.0 },
+                ],
+                attack_interval: 2.5,
+                speed_multiplier: 1.3,
+            },
+            BossPhase {
+                health_threshold: 0.25,
+                attacks: vec![
+                    BossAttack::RadialBurst { bullets: 24, speed: 8.0, damage: 1 },
+                    BossAttack::Charge { speed: 12.0, duration: 0.8 },
+                    BossAttack::Summon { kind: EnemyKind::Spitter, count: 2 },
+                    BossAttack::RadialBurst { bullets: 24, speed: 8.0, damage: 1 },
+                ],
+                attack_interval: 1.5,
+                speed_multiplier: 1.6,
+            },
+        ])
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BossPhaseChanged {
+    pub boss: Entity,
+    pub phase: usize,
+}
+
+fn boss_phases(
+    mut bosses: Query<(Entity, &mut Boss, &Health, &mut Steering)>,
+    mut phase_events: EventWriter<BossPhaseChanged>,
+){
+    for (entity, mut boss, health, mut steering) in bosses.iter_mut(){
+        let base_speed = *boss.base_speed.get_or_insert(steering.max_speed);
+        let mut changed = false;
+        while boss.phases.get(boss.phase + 1).is_some_and(|next| health.fraction() <= next.health_threshold){
+            boss.phase += 1;
+            changed = true;
+        }
+        if !changed{
+            continue;
+        }
+        let Some(phase) = boss.current_phase().cloned() else{
+            continue;
+        };
+        boss.next_attack = 0;
+        boss.attack_timer = Timer::from_seconds(phase.attack_interval, TimerMode::Repeating);
+        steering.max_speed = base_speed * phase.speed_multiplier;
+        phase_events.send(BossPhaseChanged{boss: entity, phase: boss.phase});
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn boss_attacks(
+    mut bosses: Query<(Entity, &mut Boss, &mut Transform, &mut Velocity)>,
+    player_info: Res<PlayerInfo>,
+    mut pool: ResMut<ProjectilePool>,
+    mut commands: Commands,
+    mut spawn_events: EventWriter<SpawnEnemyEvent>,
+    audio_controller: Res<AudioController>,
+    audio: Res<Audio>,
+    time: Res<Time>,
+){
+    for (entity, mut boss, mut transform, mut velocity) in bosses.iter_mut(){
+        // a charge overrides the steering until it runs out
+        if let Some((direction, timer)) = boss.charge.as_mut(){
+            timer.tick(time.delta());
+            velocity.linvel = *direction;
+            if timer.finished(){
+                boss.charge = None;
+            }
+            continue;
+        }
+
+        boss.attack_timer.tick(time.delta());
+        if !boss.attack_timer.just_finished(){
+            continue;
+        }
+        let Some(phase) = boss.current_phase() else{
+            continue;
+        };
+        if phase.attacks.is_empty(){
+            continue;
+        }
+        let attack = phase.attacks[boss.next_attack % phase.attacks.len()].clone();
+        boss.next_attack += 1;
+
+        let position = transform.translation;
+        match attack {
+            BossAttack::RadialBurst { bullets, speed, damage } => {
+                let muzzle = Vec3::new(position.x, player_info.position.y, position.z);
+                for index in 0..bullets{
+                    let angle = std::f32::consts::TAU * index as f32 / bullets as f32;
+                    let direction = Vec3::new(angle.cos(), 0.0, angle.sin());
+                    pool.spawn(
+                        &mut commands,
+                        muzzle + direction * 2.0,
+                        direction * speed,
+                        Bullet::new(damage, 4.0, 30.0).fired_by(entity, Faction::Enemy),
+                    );
+                }
+                if let Some(sound) = audio_controller.get_handle("laser"){
+                    audio.play_with_settings(sound.handle, PlaybackSettings::ONCE.with_volume(0.4));
+                }
+            }
+            BossAttack::Charge { speed, duration } => {
+                let to_player = player_info.position - position;
+                let direction = Vec3::new(to_player.x, 0.0, to_player.z).normalize_or_zero();
+                if direction != Vec3::ZERO{
+                    transform.rotation = Quat::from_axis_angle(Vec3::Y, direction.x.atan2(direction.z));
+                }
+                boss.charge = Some((direction * speed, Timer::from_seconds(duration, TimerMode::Once)));
+            }
+            BossAttack::Summon { kind, count } => {
+                for _ in 0..count{
+                    spawn_events.send(SpawnEnemyEvent{kind});
+                }
+            }
+        }
+    }
+}
+
+fn boss_phase_sounds(
+    mut phase_events: EventReader<BossPhaseChanged>,
+    audio_controller: Res<AudioController>,
+    audio: Res<Audio>,
+){
+    for _ in phase_events.iter(){
+        if let Some(sound) = audio_controller.get_handle("explosion"){
+            audio.play_with_settings(sound.handle, PlaybackSettings::ONCE.with_volume(0.6));
+        }
+    }
+}
+
+/// Plays the boss track while any boss is alive and the normal track otherwise.
+/// Without a `boss_music` handle the normal track keeps playing.
+fn boss_music(
+    bosses: Query<(), With<Boss>>,
+    mut audio_controller: ResMut<AudioController>,
+    audio: Res<Audio>,
+    sinks: Res<Assets<AudioSink>>,
+){
+    let has_boss_track = audio_controller.get_handle("boss_music").is_some();
+    let track = if bosses.is_empty() || !has_boss_track { "music" } else { "boss_music" };
+    audio_controller.play_music(track, &audio, &sinks);
+}
+
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin{
+    fn build(&self, app: &mut App) {
+        app.add_event::<BossPhaseChanged>()
+        .add_systems((
+            boss_phases.in_set(OnUpdate(AppState::InGame)),
+            boss_attacks.in_set(OnUpdate(AppState::InGame)).after(boss_phases).after(apply_steering),
+            boss_phase_sounds.in_set(OnUpdate(AppState::InGame)),
+            boss_music.in_set(OnUpdate(AppState::InGame)),
+        ));
+    }
+}
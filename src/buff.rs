@@ -0,0 +1,144 @@
+use bevy::prelude::*;
+
+use crate::app_state::AppState;
+use crate::pickup::PowerUp;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StackRule {
+    /// Picking it up again restarts the timer.
+    Refresh,
+    /// Picking it up again adds its duration to what is left, up to `max` seconds.
+    Extend { max: f32 },
+    /// Every pickup adds a stack, up to `max`, and restarts the timer.
+    Stack { max: u32 },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BuffRule {
+    /// Seconds a single pickup lasts.
+    pub duration: f32,
+    pub stacking: StackRule,
+    /// What each stack adds to the multiplier, 0.5 is +50%.
+    pub strength: f32,
+}
+
+/// How every power up behaves.
+#[derive(Resource, Clone, Debug)]
+pub struct BuffRules {
+    pub speed: BuffRule,
+    pub fire_rate: BuffRule,
+    pub damage: BuffRule,
+    /// Every stack absorbs one hit.
+    pub shield: BuffRule,
+}
+
+impl BuffRules {
+    pub fn get(&self, power_up: PowerUp) -> BuffRule {
+        match power_up {
+            PowerUp::Speed => self.speed,
+            PowerUp::FireRate => self.fire_rate,
+            PowerUp::Damage => self.damage,
+            PowerUp::Shield => self.shield,
+        }
+    }
+}
+
+impl Default for BuffRules {
+    fn default() -> Self {
+        BuffRules {
+            speed: BuffRule { duration: 8.0, stacking: StackRule::Refresh, strength: 0.5 },
+            fire_rate: BuffRule { duration: 8.0, stacking: StackRule::Stack { max: 3 }, strength: 0.5 },
+            damage: BuffRule { duration: 10.0, stacking: StackRule::Extend { max: 30.0 }, strength: 1.0 },
+            shield: BuffRule { duration: 15.0, stacking: StackRule::Stack { max: 3 }, strength: 0.0 },
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ActiveBuff {
+    pub power_up: PowerUp,
+    pub stacks: u32,
+    pub strength: f32,
+    pub timer: Timer,
+}
+
+/// Timed power ups currently affecting an entity.
+#[derive(Component, Clone, Debug, Default)]
+pub struct Buffs {
+    pub active: Vec<ActiveBuff>,
+}
+
+impl Buffs {
+    pub fn add(&mut self, power_up: PowerUp, rule: BuffRule) {
+        let Some(buff) = self.active.iter_mut().find(|buff| buff.power_up == power_up) else{
+            self.active.push(ActiveBuff {
+                power_up,
+                stacks: 1,
+                strength: rule.strength,
+                timer: Timer::from_seconds(rule.duration, TimerMode::Once),
+            });
+            return;
+        };
+        match rule.stacking {
+            StackRule::Refresh => buff.timer.reset(),
+            StackRule::Extend { max } => {
+                let remaining = (buff.timer.duration() - buff.timer.elapsed()).as_secs_f32();
+                buff.timer = Timer::from_seconds((remaining + rule.duration).min(max), TimerMode::Once);
+            }
+            StackRule::Stack { max } => {
+                buff.stacks = (buff.stacks + 1).min(max);
+                buff.timer.reset();
+            }
+        }
+    }
+
+    pub fn stacks(&self, power_up: PowerUp) -> u32 {
+        self.active.iter()
+            .find(|buff| buff.power_up == power_up)
+            .map_or(0, |buff| buff.stacks)
+    }
+
+    /// 1 with no buff, growing by the buff's strength per stack.
+    pub fn multiplier(&self, power_up: PowerUp) -> f32 {
+        self.active.iter()
+            .find(|buff| buff.power_up == power_up)
+            .map_or(1.0, |buff| 1.0 + buff.strength * buff.stacks as f32)
+    }
+
+    /// Uses up one stack, returns false if there was none.
+    pub fn consume_stack(&mut self, power_up: PowerUp) -> bool {
+        let Some(index) = self.active.iter().position(|buff| buff.power_up == power_up) else{
+            return false;
+        };
+        self.active[index].stacks -= 1;
+        if self.active[index].stacks == 0{
+            self.active.remove(index);
+        }
+        true
+    }
+}
+
+fn tick_buffs(
+    mut buffs: Query<&mut Buffs>,
+    time: Res<Time>,
+){
+    for mut buffs in buffs.iter_mut(){
+        if buffs.active.is_empty(){
+            continue;
+        }
+        for buff in buffs.active.iter_mut(){
+            buff.timer.tick(time.delta());
+        }
+        buffs.active.retain(|buff| !buff.timer.finished());
+    }
+}
+
+
+pub struct BuffPlugin;
+
+impl Plugin for BuffPlugin{
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BuffRules>()
+        .add_system(tick_buffs.in_set(OnUpdate(AppState::InGame)));
+    }
+}
@@ -1,49 +1,371 @@
 use bevy::prelude::*;
-use bevy_rapier3d::prelude::Velocity;
+use bevy_rapier3d::prelude::{Velocity, RigidBody, Collider, GravityScale, RigidBodyDisabled, ColliderDisabled};
 
 use crate::collision::*;
 use crate::audio::*;
 use crate::player::PlayerInfo;
-use crate::app_state::AppState;
+use crate::app_state::{starting_run, AppState, StateScoped};
+use crate::health::*;
+use crate::ArenaBounds;
 
-const SPEED: f32 = 10.0;
+/// Which side fired a bullet, and so what it can hit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Faction {
+    Player,
+    Enemy,
+}
 
+impl Faction {
+    pub fn bullet_kind(self) -> CollidableKind {
+        match self {
+            Faction::Player => CollidableKind::Bullet,
+            Faction::Enemy => CollidableKind::EnemyBullet,
+        }
+    }
+}
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct Bullet{
-    pub velocity: Vec3,
+    pub damage: i32,
+    /// Whoever fired it, credited with the damage it does.
+    pub owner: Option<Entity>,
+    pub faction: Faction,
+    /// The bullet expires when this runs out, wherever it is.
+    pub lifetime: Timer,
+    /// The bullet expires once it is this far from `origin`.
+    pub max_distance: f32,
+    pub origin: Vec3,
+    /// Whether to leave an `ImpactEffect` behind when it expires.
+    pub impact_effect: bool,
+}
+
+impl Bullet {
+    pub fn new(damage: i32, lifetime: f32, max_distance: f32) -> Self {
+        Bullet {
+            damage,
+            owner: None,
+            faction: Faction::Player,
+            lifetime: Timer::from_seconds(lifetime, TimerMode::Once),
+            max_distance,
+            origin: Vec3::ZERO,
+            impact_effect: true,
+        }
+    }
+
+    pub fn fired_by(mut self, owner: Entity, faction: Faction) -> Self {
+        self.owner = Some(owner);
+        self.faction = faction;
+        self
+    }
+}
+
+/// A short lived flash left where a bullet expired.
+#[derive(Component)]
+pub struct ImpactEffect{
+    pub timer: Timer,
+}
+
+/// Marks entities owned by the `ProjectilePool`. They are never despawned
+/// by gameplay code, only handed back with `ProjectilePool::release`.
+#[derive(Component)]
+pub struct Pooled;
+
+#[derive(Resource, Clone, Copy)]
+pub struct ProjectilePoolSettings{
+    /// Most bullets alive at once. Firing past it recycles the oldest bullet
+    /// of the same faction, never one fired by the other side.
+    pub capacity: usize,
+    /// How many of those enemies may hold at once, so a boss burst can't
+    /// leave the player without bullets.
+    pub enemy_capacity: usize,
+}
+
+impl Default for ProjectilePoolSettings {
+    fn default() -> Self {
+        ProjectilePoolSettings { capacity: 128, enemy_capacity: 96 }
+    }
+}
+
+/// Bullet entities and the mesh and material they all share. Idle bullets
+/// are kept hidden with their body and collider disabled until fired again.
+#[derive(Resource)]
+pub struct ProjectilePool{
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<StandardMaterial>,
+    pub enemy_material: Handle<StandardMaterial>,
+    pub impact_mesh: Handle<Mesh>,
+    pub impact_material: Handle<StandardMaterial>,
+    pub capacity: usize,
+    pub enemy_capacity: usize,
+    free: Vec<Entity>,
+    /// Oldest first, so running out recycles the bullet closest to expiring.
+    active: Vec<(Entity, Faction)>,
+    /// How many shots had to recycle a live bullet because the pool was empty.
+    pub exhausted: u32,
+    pub peak_active: usize,
+}
+
+impl ProjectilePool {
+    pub fn active(&self) -> usize {
+        self.active.len()
+    }
+
+    pub fn is_active(&self, entity: Entity) -> bool {
+        self.active.iter().any(|(active, _)| *active == entity)
+    }
+
+    /// Most bullets `faction` may have in flight at once.
+    pub fn faction_capacity(&self, faction: Faction) -> usize {
+        match faction {
+            Faction::Player => self.capacity,
+            Faction::Enemy => self.enemy_capacity.min(self.capacity),
+        }
+    }
+
+    /// Takes back the oldest bullet `faction` has in flight, if any.
+    fn recycle(&mut self, faction: Faction) -> Option<Entity> {
+        let index = self.active.iter().position(|(_, owner)| *owner == faction)?;
+        self.exhausted += 1;
+        debug!("projectile pool exhausted {} times", self.exhausted);
+        Some(self.active.remove(index).0)
+    }
+
+    /// Fires a bullet from the pool.
+    pub fn spawn(
+        &mut self,
+        commands: &mut Commands,
+        origin: Vec3,
+        velocity: Vec3,
+        mut bullet: Bullet,
+    ){
+        let in_flight = self.active.iter().filter(|(_, faction)| *faction == bullet.faction).count();
+        let entity = if in_flight >= self.faction_capacity(bullet.faction) {
+            self.recycle(bullet.faction)
+        } else {
+            self.free.pop().or_else(|| self.recycle(bullet.faction))
+        };
+        let Some(entity) = entity else{
+            return;
+        };
+        self.active.push((entity, bullet.faction));
+        self.peak_active = self.peak_active.max(self.active.len());
+
+        bullet.origin = origin;
+        let material = match bullet.faction {
+            Faction::Player => self.material.clone(),
+            Faction::Enemy => self.enemy_material.clone(),
+        };
+        commands.entity(entity)
+            .insert((
+                // a recycled bullet may have been fired by the other side
+                Collidable{kind: bullet.faction.bullet_kind()},
+                material,
+                bullet,
+                Transform::from_translation(origin).looking_at(origin + velocity, Vec3::Y),
+                // set once, the body keeps it since nothing slows it down
+                Velocity::linear(velocity),
+                Visibility::Visible,
+            ))
+            .remove::<(RigidBodyDisabled, ColliderDisabled)>();
+    }
+
+    /// Hands a bullet back to the pool. Releasing an idle bullet does nothing,
+    /// so a bullet that both hits and expires in one frame is fine.
+    pub fn release(&mut self, commands: &mut Commands, entity: Entity){
+        let Some(index) = self.active.iter().position(|(active, _)| *active == entity) else{
+            return;
+        };
+        self.active.remove(index);
+        self.free.push(entity);
+        commands.entity(entity)
+            .remove::<Bullet>()
+            .insert((
+                Velocity::zero(),
+                Visibility::Hidden,
+                RigidBodyDisabled,
+                ColliderDisabled,
+            ));
+    }
 }
 
-fn move_bullet(mut bullets: Query<(&mut Bullet, &mut Velocity)>){
-    for mut bullet in bullets.iter_mut(){
-        bullet.1.linvel = -bullet.0.velocity * SPEED;
+fn create_projectile_pool(
+    mut commands: Commands,
+    settings: Res<ProjectilePoolSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+){
+    commands.insert_resource(ProjectilePool{
+        mesh: meshes.add(Mesh::from(shape::Box::new(0.1, 0.1, 0.5))),
+        material: materials.add(Color::rgb(0.0, 0.0, 1.0).into()),
+        enemy_material: materials.add(StandardMaterial{
+            base_color: Color::rgb(1.0, 0.4, 0.0),
+            emissive: Color::rgb(1.0, 0.4, 0.0),
+            ..default()
+        }),
+        impact_mesh: meshes.add(Mesh::from(shape::UVSphere{radius: 0.2, ..default()})),
+        impact_material: materials.add(StandardMaterial{
+            base_color: Color::rgb(0.5, 0.5, 1.0),
+            emissive: Color::rgb(0.5, 0.5, 1.0),
+            ..default()
+        }),
+        capacity: settings.capacity,
+        enemy_capacity: settings.enemy_capacity,
+        free: Vec::new(),
+        active: Vec::new(),
+        exhausted: 0,
+        peak_active: 0,
+    });
+}
+
+// the pooled entities belong to the scene and are cleaned up with it, so
+// every run fills the pool again.
+fn fill_projectile_pool(
+    mut commands: Commands,
+    mut pool: ResMut<ProjectilePool>,
+){
+    pool.active.clear();
+    pool.free.clear();
+    for _ in 0..pool.capacity{
+        let entity = commands
+            .spawn((
+                RigidBody::Dynamic,
+                Pooled,
+                StateScoped(AppState::InGame),
+                Collider::cuboid(0.05, 0.05, 0.25),
+                Collidable{kind: CollidableKind::Bullet},
+                Velocity::zero(),
+                GravityScale(0.0),
+                RigidBodyDisabled,
+                ColliderDisabled,
+                PbrBundle {
+                    mesh: pool.mesh.clone(),
+                    material: pool.material.clone(),
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+            ))
+            .id();
+        pool.free.push(entity);
     }
 }
 
-//fn spawn_bullet()
+fn expire_bullets(
+    mut bullets: Query<(Entity, &mut Bullet, &Transform)>,
+    mut commands: Commands,
+    mut pool: ResMut<ProjectilePool>,
+    bounds: Res<ArenaBounds>,
+    time: Res<Time>,
+){
+    for (entity, mut bullet, transform) in bullets.iter_mut(){
+        bullet.lifetime.tick(time.delta());
+        let travelled = transform.translation.distance(bullet.origin);
+        if !bullet.lifetime.finished() && travelled < bullet.max_distance && bounds.contains(transform.translation){
+            continue;
+        }
+        if bullet.impact_effect{
+            commands.spawn((
+                ImpactEffect{timer: Timer::from_seconds(0.2, TimerMode::Once)},
+                StateScoped(AppState::InGame),
+                PbrBundle {
+                    mesh: pool.impact_mesh.clone(),
+                    material: pool.impact_material.clone(),
+                    transform: Transform::from_translation(transform.translation),
+                    ..default()
+                },
+            ));
+        }
+        pool.release(&mut commands, entity);
+    }
+}
 
+fn fade_impact_effects(
+    mut effects: Query<(Entity, &mut ImpactEffect, &mut Transform)>,
+    mut commands: Commands,
+    time: Res<Time>,
+){
+    for (entity, mut effect, mut transform) in effects.iter_mut(){
+        effect.timer.tick(time.delta());
+        transform.scale = Vec3::splat(1.0 - effect.timer.percent());
+        if effect.timer.finished(){
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+pub struct BulletHitsEnemy;
+
+impl CollisionPair for BulletHitsEnemy{
+    const THIS: CollidableKind = CollidableKind::Bullet;
+    const OTHER: CollidableKind = CollidableKind::Enemy;
+}
+
+pub struct BulletHitsWall;
+
+impl CollisionPair for BulletHitsWall{
+    const THIS: CollidableKind = CollidableKind::Bullet;
+    const OTHER: CollidableKind = CollidableKind::Wall;
+}
+
+pub struct EnemyBulletHitsPlayer;
+
+impl CollisionPair for EnemyBulletHitsPlayer{
+    const THIS: CollidableKind = CollidableKind::EnemyBullet;
+    const OTHER: CollidableKind = CollidableKind::Player;
+}
+
+pub struct EnemyBulletHitsWall;
+
+impl CollisionPair for EnemyBulletHitsWall{
+    const THIS: CollidableKind = CollidableKind::EnemyBullet;
+    const OTHER: CollidableKind = CollidableKind::Wall;
+}
+
+#[allow(clippy::too_many_arguments)]
 fn bullet_collision(
-    mut bullets: Query<(Entity, &CollisionTag), With<Bullet>>,
+    mut enemy_hits: EventReader<PairCollisionEvent<BulletHitsEnemy>>,
+    mut wall_hits: EventReader<PairCollisionEvent<BulletHitsWall>>,
+    mut player_hits: EventReader<PairCollisionEvent<EnemyBulletHitsPlayer>>,
+    mut enemy_wall_hits: EventReader<PairCollisionEvent<EnemyBulletHitsWall>>,
+    bullets: Query<&Bullet>,
     player_info: Res<PlayerInfo>,
     mut commands: Commands,
+    mut pool: ResMut<ProjectilePool>,
+    mut damage_events: EventWriter<DamageEvent>,
     audio_controller: Res<AudioController>,
     audio: Res<Audio>,
 ){
+    let started = |phase: ContactPhase| phase == ContactPhase::Started;
+    let hits = enemy_hits.iter().filter(|hit| started(hit.phase)).map(|hit| (hit.this, Some(hit.other), Faction::Player))
+        .chain(wall_hits.iter().filter(|hit| started(hit.phase)).map(|hit| (hit.this, None, Faction::Player)))
+        .chain(player_hits.iter().filter(|hit| started(hit.phase)).map(|hit| (hit.this, Some(hit.other), Faction::Enemy)))
+        .chain(enemy_wall_hits.iter().filter(|hit| started(hit.phase)).map(|hit| (hit.this, None, Faction::Enemy)));
 
-    for (entity, tag) in bullets.iter_mut(){
-        println!("{:?}", tag.other.kind);
-        match tag.other.kind {
-            CollidableKind::Enemy | CollidableKind::Wall => {
-                if let Some(slam) = audio_controller.get_handle("slam"){
-                    audio.play_spatial_with_settings(
-                        slam.handle, PlaybackSettings::ONCE.with_volume(0.5),
-                        Transform::from_translation(player_info.position),
-                        4.0,
-                        tag.this.transform.translation);
-                }
-                commands.entity(entity).despawn();
-            }
-                _ => {}
+    for (bullet, target, faction) in hits{
+        // a bullet can touch several things in the same step, only the
+        // first one counts
+        let Ok(bullet_info) = bullets.get(bullet.entity) else{
+            continue;
+        };
+        // a recycled bullet can report one step of contacts under its old
+        // side's collision groups
+        if bullet_info.faction != faction || !pool.is_active(bullet.entity){
+            continue;
+        }
+        pool.release(&mut commands, bullet.entity);
+        if let Some(target) = target{
+            damage_events.send(DamageEvent{
+                target: target.entity,
+                source: Some(bullet_info.owner.unwrap_or(bullet.entity)),
+                amount: bullet_info.damage,
+                kind: DamageKind::Projectile,
+            });
+        }
+        if let Some(slam) = audio_controller.get_handle("slam"){
+            audio.play_spatial_with_settings(
+                slam.handle, PlaybackSettings::ONCE.with_volume(0.5),
+                Transform::from_translation(player_info.position),
+                4.0,
+                bullet.transform.translation);
         }
     }
 }
@@ -53,8 +375,16 @@ pub struct BulletPlugin;
 
 impl Plugin for BulletPlugin{
     fn build(&self, app: &mut App) {
-        app.add_systems((
-            move_bullet.in_set(OnUpdate(AppState::InGame)),
+        app.init_resource::<ProjectilePoolSettings>()
+        .add_startup_system(create_projectile_pool)
+        .add_system(fill_projectile_pool.in_schedule(OnEnter(AppState::InGame)).run_if(starting_run))
+        .add_collision_pair::<BulletHitsEnemy>()
+        .add_collision_pair::<BulletHitsWall>()
+        .add_collision_pair::<EnemyBulletHitsPlayer>()
+        .add_collision_pair::<EnemyBulletHitsWall>()
+        .add_systems((
+            expire_bullets.in_set(OnUpdate(AppState::InGame)),
+            fade_impact_effects.in_set(OnUpdate(AppState::InGame)),
             bullet_collision.in_set(OnUpdate(AppState::InGame)),
         ));
     }
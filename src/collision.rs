@@ -1,5 +1,7 @@
+use std::marker::PhantomData;
+
 use bevy::{prelude::*,};
-use bevy_rapier3d::prelude::*;
+use bevy_rapier3d::{prelude::*, rapier::geometry::CollisionEventFlags};
 
 #[derive(Clone, Debug, Copy, PartialEq)]
 pub enum CollidableKind {
@@ -8,6 +10,119 @@ pub enum CollidableKind {
     Enemy,
     Ground,
     Wall,
+    Trigger,
+    /// Projectiles fired by enemies.
+    EnemyBullet,
+}
+
+const KIND_COUNT: usize = 7;
+
+impl CollidableKind {
+    pub const ALL: [CollidableKind; KIND_COUNT] = [
+        CollidableKind::Player,
+        CollidableKind::Bullet,
+        CollidableKind::Enemy,
+        CollidableKind::Ground,
+        CollidableKind::Wall,
+        CollidableKind::Trigger,
+        CollidableKind::EnemyBullet,
+    ];
+
+    /// The Rapier group every collider of this kind is a member of.
+    pub fn group(self) -> Group {
+        Group::from_bits_truncate(1 << self as u32)
+    }
+}
+
+/// How two kinds of collidable treat each other.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum Interaction {
+    /// They pass through each other and nothing is reported.
+    Ignore,
+    /// They push each other but no collision events or contacts are reported.
+    Solve,
+    /// Collision events and contacts are reported but they pass through each other.
+    Detect,
+    /// They push each other and collisions are reported.
+    Full,
+}
+
+impl Interaction {
+    pub fn solves(self) -> bool {
+        matches!(self, Interaction::Solve | Interaction::Full)
+    }
+
+    pub fn reports(self) -> bool {
+        matches!(self, Interaction::Detect | Interaction::Full)
+    }
+}
+
+/// Symmetric table of how every pair of `CollidableKind`s interacts. The
+/// `CollisionGroups` and `SolverGroups` of every `Collidable` are derived from
+/// it, so changing the resource at runtime re-applies it to the whole world.
+#[derive(Resource, Clone, Debug)]
+pub struct CollisionMatrix {
+    interactions: [[Interaction; KIND_COUNT]; KIND_COUNT],
+}
+
+impl CollisionMatrix {
+    /// A matrix where every pair interacts the same way.
+    pub fn filled(interaction: Interaction) -> Self {
+        CollisionMatrix { interactions: [[interaction; KIND_COUNT]; KIND_COUNT] }
+    }
+
+    pub fn set(&mut self, a: CollidableKind, b: CollidableKind, interaction: Interaction) -> &mut Self {
+        self.interactions[a as usize][b as usize] = interaction;
+        self.interactions[b as usize][a as usize] = interaction;
+        self
+    }
+
+    pub fn get(&self, a: CollidableKind, b: CollidableKind) -> Interaction {
+        self.interactions[a as usize][b as usize]
+    }
+
+    fn filter(&self, kind: CollidableKind, include: fn(Interaction) -> bool) -> Group {
+        CollidableKind::ALL.iter()
+            .filter(|other| include(self.get(kind, **other)))
+            .fold(Group::NONE, |groups, other| groups | other.group())
+    }
+
+    pub fn collision_groups(&self, kind: CollidableKind) -> CollisionGroups {
+        CollisionGroups::new(kind.group(), self.filter(kind, |interaction| interaction != Interaction::Ignore))
+    }
+
+    pub fn solver_groups(&self, kind: CollidableKind) -> SolverGroups {
+        SolverGroups::new(kind.group(), self.filter(kind, Interaction::solves))
+    }
+}
+
+impl Default for CollisionMatrix {
+    fn default() -> Self {
+        use CollidableKind::*;
+        let mut matrix = CollisionMatrix::filled(Interaction::Solve);
+        matrix
+            .set(Player, Enemy, Interaction::Full)
+            .set(Player, Bullet, Interaction::Ignore)
+            .set(Bullet, Bullet, Interaction::Ignore)
+            .set(Bullet, Ground, Interaction::Ignore)
+            .set(Bullet, Enemy, Interaction::Detect)
+            .set(Bullet, Wall, Interaction::Detect)
+            .set(Ground, Wall, Interaction::Ignore);
+        for kind in CollidableKind::ALL{
+            matrix.set(Trigger, kind, Interaction::Ignore);
+        }
+        matrix
+            .set(Trigger, Player, Interaction::Detect)
+            .set(Trigger, Enemy, Interaction::Detect);
+        // enemy bullets only hurt the player and stop at walls
+        for kind in CollidableKind::ALL{
+            matrix.set(EnemyBullet, kind, Interaction::Ignore);
+        }
+        matrix
+            .set(EnemyBullet, Player, Interaction::Detect)
+            .set(EnemyBullet, Wall, Interaction::Detect);
+        matrix
+    }
 }
 
 #[derive(Clone, Debug, Copy)]
@@ -17,16 +132,129 @@ pub struct ObjectInfo{
     pub transform: Transform,
 }
 
-#[derive(Component, Debug, Clone)]
-pub struct CollisionTag {
-    pub other: ObjectInfo,
-    pub this: ObjectInfo,
-}
 #[derive(Component)]
 pub struct Collidable {
     pub kind: CollidableKind,
 }
 
+/// Where a contact is in its lifetime. A contact is `Started` for the frame
+/// Rapier reports it, `Persisting` while the colliders keep touching and
+/// `Stopped` for the single frame after Rapier reports the separation.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum ContactPhase {
+    Started,
+    Persisting,
+    Stopped,
+}
+
+#[derive(Clone, Debug, Copy)]
+pub struct Contact {
+    pub other: ObjectInfo,
+    /// World space normal pointing from this entity towards `other`.
+    pub normal: Vec3,
+    /// Sum of the normal impulses applied at every contact point this step.
+    pub impulse: f32,
+    pub phase: ContactPhase,
+    /// Set for the frame the contact began, even if it also stopped that frame.
+    pub started: bool,
+}
+
+/// Every collidable currently touching this entity. Inserted automatically
+/// on anything with a `Collidable` right before Rapier's events are read in
+/// `PostUpdate`, so a collider spawned this frame doesn't miss its first
+/// contact, and kept in sync there so it can be read by any system in `Update`.
+#[derive(Component, Debug, Clone, Default)]
+pub struct Contacts {
+    pub contacts: Vec<Contact>,
+}
+
+impl Contacts {
+    pub fn iter(&self) -> impl Iterator<Item = &Contact> {
+        self.contacts.iter()
+    }
+
+    pub fn started(&self) -> impl Iterator<Item = &Contact> {
+        self.contacts.iter().filter(|contact| contact.started)
+    }
+
+    pub fn with_kind(&self, kind: CollidableKind) -> impl Iterator<Item = &Contact> {
+        self.contacts.iter().filter(move |contact| contact.other.kind == kind)
+    }
+
+    pub fn get(&self, other: Entity) -> Option<&Contact> {
+        self.contacts.iter().find(|contact| contact.other.entity == other)
+    }
+
+    fn get_mut(&mut self, other: Entity) -> Option<&mut Contact> {
+        self.contacts.iter_mut().find(|contact| contact.other.entity == other)
+    }
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum TriggerKind {
+    Pickup,
+    Hazard,
+    SpawnBlocker,
+    ArenaExit,
+}
+
+/// A sensor volume that reports what enters and leaves it instead of
+/// taking part in contacts. `occupants` lists every collidable inside it.
+#[derive(Component, Debug, Clone)]
+pub struct TriggerVolume {
+    pub kind: TriggerKind,
+    pub occupants: Vec<ObjectInfo>,
+}
+
+impl TriggerVolume {
+    pub fn new(kind: TriggerKind) -> Self {
+        TriggerVolume { kind, occupants: Vec::new() }
+    }
+
+    pub fn is_occupied_by(&self, kind: CollidableKind) -> bool {
+        self.occupants.iter().any(|occupant| occupant.kind == kind)
+    }
+}
+
+#[derive(Bundle)]
+pub struct TriggerBundle {
+    pub trigger: TriggerVolume,
+    pub collidable: Collidable,
+    pub collider: Collider,
+    pub sensor: Sensor,
+    pub active_events: ActiveEvents,
+    // sensors have no body, so they need this to notice kinematic enemies
+    pub active_collision_types: ActiveCollisionTypes,
+    pub transform: TransformBundle,
+}
+
+impl TriggerBundle {
+    pub fn new(kind: TriggerKind, collider: Collider, transform: Transform) -> Self {
+        TriggerBundle {
+            trigger: TriggerVolume::new(kind),
+            collidable: Collidable{kind: CollidableKind::Trigger},
+            collider,
+            sensor: Sensor,
+            active_events: ActiveEvents::COLLISION_EVENTS,
+            active_collision_types: ActiveCollisionTypes::all(),
+            transform: TransformBundle::from_transform(transform),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TriggerEnterEvent {
+    pub trigger: ObjectInfo,
+    pub kind: TriggerKind,
+    pub entrant: ObjectInfo,
+}
+#[derive(Clone)]
+pub struct TriggerExitEvent {
+    pub trigger: ObjectInfo,
+    pub kind: TriggerKind,
+    pub entrant: ObjectInfo,
+}
+
 #[derive(Clone)]
 pub struct CollisionStartEvent(pub ObjectInfo, pub ObjectInfo);
 #[derive(Clone)]
@@ -34,149 +262,300 @@ pub struct CollisionStayEvent(pub ObjectInfo, pub ObjectInfo);
 #[derive(Clone)]
 pub struct CollisionStopEvent(pub ObjectInfo, pub ObjectInfo);
 
+/// A pair of kinds some system wants to hear about, registered with
+/// `App::add_collision_pair`. Collisions between the two kinds are delivered
+/// as `PairCollisionEvent<Self>` with `this` always being of kind `THIS`.
+pub trait CollisionPair: Send + Sync + 'static {
+    const THIS: CollidableKind;
+    const OTHER: CollidableKind;
+}
+
+pub struct PairCollisionEvent<P: CollisionPair> {
+    pub this: ObjectInfo,
+    pub other: ObjectInfo,
+    pub phase: ContactPhase,
+    _pair: PhantomData<P>,
+}
+
+impl<P: CollisionPair> PairCollisionEvent<P> {
+    /// Orders `a` and `b` to match the pair, or `None` if they are not of its kinds.
+    fn new(a: ObjectInfo, b: ObjectInfo, phase: ContactPhase) -> Option<Self> {
+        let (this, other) = if a.kind == P::THIS && b.kind == P::OTHER {
+            (a, b)
+        } else if b.kind == P::THIS && a.kind == P::OTHER {
+            (b, a)
+        } else {
+            return None;
+        };
+        Some(PairCollisionEvent { this, other, phase, _pair: PhantomData })
+    }
+}
 
+pub trait AddCollisionPair {
+    fn add_collision_pair<P: CollisionPair>(&mut self) -> &mut Self;
+}
 
-#[derive(Resource)]
-pub struct CollisionEvents {
-    pub start_events: Vec<CollisionStartEvent>,
-    pub stay_events: Vec<CollisionStayEvent>,
-    pub stop_events: Vec<CollisionStopEvent>,
+impl AddCollisionPair for App {
+    fn add_collision_pair<P: CollisionPair>(&mut self) -> &mut Self {
+        self.add_event::<PairCollisionEvent<P>>()
+            .add_system(
+                dispatch_pair_events::<P>
+                    .in_base_set(CoreSet::PostUpdate)
+                    .after(update_contacts)
+            )
+    }
 }
 
-fn collision_rapier_handler(
+fn insert_contacts(
+    mut commands: Commands,
+    collidables: Query<Entity, (With<Collidable>, Without<Contacts>)>,
+){
+    for entity in collidables.iter(){
+        commands.entity(entity).insert(Contacts::default());
+    }
+}
+
+/// Sits between the end of Update and Rapier's backend sync, so colliders
+/// spawned, recycled or retyped this frame step with their groups already set.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+#[system_set(base)]
+struct CollisionGroupSync;
+
+fn apply_collision_groups(
     mut commands: Commands,
+    matrix: Res<CollisionMatrix>,
+    collidables: Query<(Entity, &Collidable)>,
+    changed: Query<(Entity, &Collidable), Changed<Collidable>>,
+){
+    let apply = |(entity, collidable): (Entity, &Collidable)| {
+        commands.entity(entity).insert((
+            matrix.collision_groups(collidable.kind),
+            matrix.solver_groups(collidable.kind),
+        ));
+    };
+    if matrix.is_changed(){
+        collidables.iter().for_each(apply);
+    }else{
+        changed.iter().for_each(apply);
+    }
+}
+
+fn collision_rapier_handler(
+    matrix: Res<CollisionMatrix>,
     mut collision_events: EventReader<CollisionEvent>,
-    collidables: Query<&Collidable, Without<Sensor>>,
-    transforms: Query<&Transform, Without<Sensor>>,
+    collidables: Query<(&Collidable, &Transform), Without<Sensor>>,
+    mut contacts: Query<(Entity, &mut Contacts)>,
     mut event_col_start: EventWriter<CollisionStartEvent>,
+    mut event_col_stop: EventWriter<CollisionStopEvent>,
 ) {
+    // contacts that stopped last frame have been seen by everyone, drop them
+    // before applying this step's events.
+    for (_, mut entity_contacts) in contacts.iter_mut(){
+        entity_contacts.contacts.retain(|contact| contact.phase != ContactPhase::Stopped);
+        for contact in entity_contacts.contacts.iter_mut(){
+            contact.phase = ContactPhase::Persisting;
+            contact.started = false;
+        }
+    }
+
     for collision_event in collision_events.iter() {
         match collision_event {
             CollisionEvent::Started(a, b, _) => {
-
-                let (Ok(col_a), Ok(tran_a)) = (collidables.get(*a), transforms.get(*a)) 
+                let (Ok((col_a, tran_a)), Ok((col_b, tran_b))) = (collidables.get(*a), collidables.get(*b))
                 else{
                     continue;
                 };
 
-                let (Ok(col_b), Ok(tran_b)) = (collidables.get(*b), transforms.get(*b))
-                else{
+                // the pair still touches, it just isn't interesting to anyone
+                if !matrix.get(col_a.kind, col_b.kind).reports(){
                     continue;
-                };
+                }
 
-                //println!("{:?} {:?}", col_a.kind, col_b.kind);
+                let objectinfo_a = ObjectInfo{entity: *a, kind: col_a.kind, transform: *tran_a};
+                let objectinfo_b = ObjectInfo{entity: *b, kind: col_b.kind, transform: *tran_b};
 
-                let objectinfo_a = ObjectInfo{entity: *a, kind: col_a.kind.clone(), transform: *tran_a};
-                let objectinfo_b = ObjectInfo{entity: *b, kind: col_b.kind.clone(), transform: *tran_b};
+                for (this, other) in [(objectinfo_a, objectinfo_b), (objectinfo_b, objectinfo_a)]{
+                    if let Ok((_, mut entity_contacts)) = contacts.get_mut(this.entity){
+                        // a pair can stop and start again within the same frame
+                        entity_contacts.contacts.retain(|contact| contact.other.entity != other.entity);
+                        entity_contacts.contacts.push(Contact{
+                            other,
+                            normal: Vec3::ZERO,
+                            impulse: 0.0,
+                            phase: ContactPhase::Started,
+                            started: true,
+                        });
+                    }
+                }
 
                 event_col_start.send(CollisionStartEvent(objectinfo_a, objectinfo_b));
-                //event_col_start.send(CollisionStartEvent(objectinfo_b, objectinfo_a));
-
-                commands.entity(*a)
-                    .insert(CollisionTag {
-                        other: objectinfo_b,
-                        this: objectinfo_a,
-                    });
-
-                commands.entity(*b)
-                    .insert(CollisionTag {
-                        other: objectinfo_a,
-                        this: objectinfo_b,
-                    });
             }
-            _ => {}
+            CollisionEvent::Stopped(a, b, _) => {
+                // either side may already be despawned, so recover the info
+                // from whatever the survivor remembered about it.
+                let mut infos = [None, None];
+                for (i, (this, other)) in [(*a, *b), (*b, *a)].into_iter().enumerate(){
+                    if let Ok((_, mut entity_contacts)) = contacts.get_mut(this){
+                        if let Some(contact) = entity_contacts.get_mut(other){
+                            contact.phase = ContactPhase::Stopped;
+                            infos[1 - i] = Some(contact.other);
+                        }
+                    }
+                }
+                let (Some(objectinfo_a), Some(objectinfo_b)) = (infos[0], infos[1]) else{
+                    if let Some(info) = infos[0].or(infos[1]){
+                        debug!("contact with {:?} stopped after its partner was removed", info.kind);
+                    }
+                    continue;
+                };
+                event_col_stop.send(CollisionStopEvent(objectinfo_a, objectinfo_b));
+            }
         }
     }
 }
 
-fn cleanup_collisions(
-    mut commands: Commands,
-    mut collision_tags: Query<(Entity, &mut CollisionTag)>,
+fn trigger_rapier_handler(
+    mut collision_events: EventReader<CollisionEvent>,
+    collidables: Query<(&Collidable, &Transform), Without<Sensor>>,
+    mut triggers: Query<(&mut TriggerVolume, &Transform)>,
+    mut event_trigger_enter: EventWriter<TriggerEnterEvent>,
+    mut event_trigger_exit: EventWriter<TriggerExitEvent>,
 ){
-    for (entity, _collision_tag) in collision_tags.iter_mut(){
-        commands.entity(entity).remove::<CollisionTag>();
+    for collision_event in collision_events.iter() {
+        match collision_event {
+            CollisionEvent::Started(a, b, flags) if flags.contains(CollisionEventFlags::SENSOR) => {
+                let (trigger_entity, entrant_entity) = if triggers.contains(*a) { (*a, *b) } else { (*b, *a) };
+                let (Ok((mut trigger, trigger_transform)), Ok((collidable, transform))) =
+                    (triggers.get_mut(trigger_entity), collidables.get(entrant_entity))
+                else{
+                    continue;
+                };
+                let entrant = ObjectInfo{entity: entrant_entity, kind: collidable.kind, transform: *transform};
+                trigger.occupants.retain(|occupant| occupant.entity != entrant_entity);
+                trigger.occupants.push(entrant);
+                event_trigger_enter.send(TriggerEnterEvent{
+                    trigger: ObjectInfo{entity: trigger_entity, kind: CollidableKind::Trigger, transform: *trigger_transform},
+                    kind: trigger.kind,
+                    entrant,
+                });
+            }
+            CollisionEvent::Stopped(a, b, flags) if flags.contains(CollisionEventFlags::SENSOR) => {
+                let (trigger_entity, entrant_entity) = if triggers.contains(*a) { (*a, *b) } else { (*b, *a) };
+                let Ok((mut trigger, trigger_transform)) = triggers.get_mut(trigger_entity) else{
+                    continue;
+                };
+                let Some(index) = trigger.occupants.iter().position(|occupant| occupant.entity == entrant_entity) else{
+                    continue;
+                };
+                let mut entrant = trigger.occupants.remove(index);
+                if let Ok((_, transform)) = collidables.get(entrant_entity){
+                    entrant.transform = *transform;
+                }
+                event_trigger_exit.send(TriggerExitEvent{
+                    trigger: ObjectInfo{entity: trigger_entity, kind: CollidableKind::Trigger, transform: *trigger_transform},
+                    kind: trigger.kind,
+                    entrant,
+                });
+            }
+            _ => {}
+        }
     }
 }
 
-fn collision_event_accumulator(
-    mut collison_start_event: EventReader<CollisionStartEvent>,
-    mut collison_stay_event: EventReader<CollisionStayEvent>,
+fn update_contacts(
     context: Res<RapierContext>,
-    mut events: ResMut<CollisionEvents>,
+    transforms: Query<&Transform>,
+    mut contacts: Query<(Entity, &mut Contacts)>,
+    mut event_col_stay: EventWriter<CollisionStayEvent>,
 ){
-    for event in collison_start_event.iter(){
-        let objectinfo_a = event.0;
-        let objectinfo_b = event.1;
-        
-        let contact_pair = context.contact_pair(objectinfo_a.entity, objectinfo_b.entity);
-        
-        match contact_pair{
-            Some(contact_pair) =>{
-                if contact_pair.has_any_active_contacts(){
-                    events.stay_events.push(CollisionStayEvent(objectinfo_a, objectinfo_b));
-                }
+    let mut stay_events = Vec::new();
+    for (entity, mut entity_contacts) in contacts.iter_mut(){
+        for contact in entity_contacts.contacts.iter_mut(){
+            if contact.phase == ContactPhase::Stopped{
+                continue;
+            }
+            if let Ok(transform) = transforms.get(contact.other.entity){
+                contact.other.transform = *transform;
             }
-            None =>{
-                events.stop_events.push(CollisionStopEvent(objectinfo_b, objectinfo_a));
+            let Some(contact_pair) = context.contact_pair(entity, contact.other.entity) else{
+                continue;
+            };
+            let flip = if contact_pair.collider1() == entity { 1.0 } else { -1.0 };
+            let mut normal = Vec3::ZERO;
+            let mut impulse = 0.0;
+            for manifold in contact_pair.manifolds(){
+                normal += manifold.normal();
+                impulse += manifold.points().map(|point| point.impulse()).sum::<f32>();
+            }
+            contact.normal = (normal * flip).normalize_or_zero();
+            contact.impulse = impulse;
+
+            if contact.phase == ContactPhase::Persisting && entity < contact.other.entity{
+                stay_events.push((entity, contact.other));
             }
         }
     }
 
-    for event in collison_stay_event.iter(){
-        let objectinfo_a = event.0;
-        let objectinfo_b = event.1;
-        
-        let contact_pair = context.contact_pair(objectinfo_a.entity, objectinfo_b.entity);
-        
-        match contact_pair{
-            Some(contact_pair) =>{
-                if contact_pair.has_any_active_contacts(){
-                    events.stay_events.push(CollisionStayEvent(objectinfo_a, objectinfo_b));
-                }
-            }
-            None =>{
-                events.stop_events.push(CollisionStopEvent(objectinfo_a, objectinfo_b));
-            }
+    // the other side of a pair knows how to describe this entity
+    for (entity, other) in stay_events{
+        let Ok((_, other_contacts)) = contacts.get(other.entity) else{
+            continue;
+        };
+        if let Some(contact) = other_contacts.get(entity){
+            event_col_stay.send(CollisionStayEvent(contact.other, other));
         }
     }
 }
 
-fn collision_event_propegator(
-    mut stay_event_writer: EventWriter<CollisionStayEvent>,
-    mut stop_event_writer: EventWriter<CollisionStopEvent>,
-    mut collision_events: ResMut<CollisionEvents>,
+fn dispatch_pair_events<P: CollisionPair>(
+    mut col_start_events: EventReader<CollisionStartEvent>,
+    mut col_stay_events: EventReader<CollisionStayEvent>,
+    mut col_stop_events: EventReader<CollisionStopEvent>,
+    mut pair_events: EventWriter<PairCollisionEvent<P>>,
 ){
-    //println!("{}", collision_events.stay_events.len());
-    for event in collision_events.stay_events.iter(){
-        stay_event_writer.send(event.clone());
+    for event in col_start_events.iter(){
+        if let Some(pair_event) = PairCollisionEvent::new(event.0, event.1, ContactPhase::Started){
+            pair_events.send(pair_event);
+        }
+    }
+    for event in col_stay_events.iter(){
+        if let Some(pair_event) = PairCollisionEvent::new(event.0, event.1, ContactPhase::Persisting){
+            pair_events.send(pair_event);
+        }
     }
-    for event in collision_events.stop_events.iter(){
-        stop_event_writer.send(event.clone());
+    for event in col_stop_events.iter(){
+        if let Some(pair_event) = PairCollisionEvent::new(event.0, event.1, ContactPhase::Stopped){
+            pair_events.send(pair_event);
+        }
     }
-    collision_events.stay_events.clear();
-    collision_events.stay_events.shrink_to_fit();
-    collision_events.stop_events.clear();
-    collision_events.stop_events.shrink_to_fit();
 }
 
 
-
-
 pub struct CollisionPlugin;
 
 impl Plugin for CollisionPlugin{
     fn build(&self, app: &mut App) {
-        app.insert_resource(CollisionEvents{
-            start_events: Vec::new(),
-            stay_events: Vec::new(),
-            stop_events: Vec::new(),
-        })
-        .add_system(collision_rapier_handler.in_base_set(CoreSet::PostUpdateFlush))
+        app.init_resource::<CollisionMatrix>()
         .add_event::<CollisionStartEvent>()
         .add_event::<CollisionStayEvent>()
         .add_event::<CollisionStopEvent>()
-        .add_system(collision_event_accumulator)
-        .add_system(collision_event_propegator.after(collision_event_accumulator))
-        .add_system(cleanup_collisions.in_base_set(CoreSet::PostUpdate));
+        .add_event::<TriggerEnterEvent>()
+        .add_event::<TriggerExitEvent>()
+        .configure_set(
+            CollisionGroupSync
+                .after(CoreSet::UpdateFlush)
+                .before(PhysicsSet::SyncBackend)
+        )
+        .add_systems(
+            (apply_collision_groups, apply_system_buffers)
+                .chain()
+                .in_base_set(CollisionGroupSync)
+        )
+        .add_systems(
+            (insert_contacts, apply_system_buffers, collision_rapier_handler, update_contacts)
+                .chain()
+                .in_base_set(CoreSet::PostUpdate)
+        )
+        .add_system(trigger_rapier_handler.in_base_set(CoreSet::PostUpdate));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,253 @@
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use rand::Rng;
+
+use crate::app_state::{starting_run, AppState};
+use crate::audio::*;
+use crate::enemy::{Enemy, EnemyKind, EnemyRegistry, PendingSpawns, SpawnTelegraph};
+
+#[derive(Clone, Debug)]
+pub struct WaveGroup {
+    pub kind: EnemyKind,
+    pub count: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Wave {
+    pub groups: Vec<WaveGroup>,
+    /// Seconds between two enemies of this wave appearing.
+    pub spawn_interval: f32,
+    /// Seconds of calm before the wave starts.
+    pub intermission: f32,
+}
+
+impl Wave {
+    pub fn new(spawn_interval: f32, intermission: f32) -> Self {
+        Wave { groups: Vec::new(), spawn_interval, intermission }
+    }
+
+    pub fn with(mut self, kind: EnemyKind, count: u32) -> Self {
+        self.groups.push(WaveGroup { kind, count });
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DirectorPhase {
+    /// Waiting out the wave's intermission.
+    Intermission,
+    /// Enemies of the wave are still being spawned.
+    Spawning,
+    /// Everything is spawned, waiting for the player to clear it.
+    Fighting,
+}
+
+/// Runs the scripted waves in order, then keeps generating harder ones.
+#[derive(Resource, Clone, Debug)]
+pub struct SpawnDirector {
+    pub script: Vec<Wave>,
+    /// The wave in progress, 0 before the first one starts.
+    pub wave: u32,
+    pub phase: DirectorPhase,
+    /// Multiplies the size of procedural waves. Grows with every wave and
+    /// with time spent in the run.
+    pub difficulty: f32,
+    pub difficulty_per_wave: f32,
+    pub difficulty_per_minute: f32,
+    /// Every this many waves a procedural wave is led by a boss, 0 for never.
+    pub boss_every: u32,
+    /// Enemies still to come plus enemies alive.
+    pub remaining: u32,
+    timer: Timer,
+    queue: Vec<EnemyKind>,
+    upcoming: Option<Wave>,
+}
+
+impl SpawnDirector {
+    pub fn new(script: Vec<Wave>) -> Self {
+        let mut director = SpawnDirector {
+            script,
+            wave: 0,
+            phase: DirectorPhase::Intermission,
+            difficulty: 1.0,
+            difficulty_per_wave: 0.25,
+            difficulty_per_minute: 0.1,
+            boss_every: 5,
+            remaining: 0,
+            timer: Timer::default(),
+            queue: Vec::new(),
+            upcoming: None,
+        };
+        director.restart();
+        director
+    }
+
+    /// Goes back to before the first wave, keeping the script and tuning.
+    pub fn restart(&mut self) {
+        self.wave = 0;
+        self.difficulty = 1.0;
+        self.remaining = 0;
+        self.queue.clear();
+        self.upcoming = self.script.first().cloned();
+        self.enter_intermission();
+    }
+
+    fn enter_intermission(&mut self) {
+        let intermission = self.upcoming.as_ref().map_or(3.0, |wave| wave.intermission);
+        self.timer = Timer::new(Duration::from_secs_f32(intermission), TimerMode::Once);
+        self.phase = DirectorPhase::Intermission;
+    }
+
+    /// The wave after the current one, from the script while it lasts.
+    fn next_wave(&self, registry: &EnemyRegistry) -> Wave {
+        if let Some(wave) = self.script.get(self.wave as usize){
+            return wave.clone();
+        }
+        let mut rng = rand::thread_rng();
+        let mut budget = ((6 + 2 * self.wave) as f32 * self.difficulty) as u32;
+        let mut wave = Wave::new((1.0 - 0.05 * self.wave as f32).max(0.2), 5.0);
+        if (self.wave + 1).checked_rem(self.boss_every) == Some(0){
+            // the boss summons its own help, so bring fewer of the rest
+            wave = wave.with(EnemyKind::Boss, 1);
+            budget /= 2;
+        }
+        for _ in 0..budget{
+            if let Some(enemy_type) = registry.choose(&mut rng){
+                wave = wave.with(enemy_type.kind, 1);
+            }
+        }
+        wave
+    }
+
+    fn start_wave(&mut self, wave: Wave) {
+        self.wave += 1;
+        self.queue = wave.groups.iter()
+            .flat_map(|group| (0..group.count).map(move |_| group.kind))
+            .collect();
+        // mix the groups instead of spawning each one as a block
+        rand::thread_rng().shuffle(&mut self.queue);
+        self.timer = Timer::from_seconds(wave.spawn_interval, TimerMode::Repeating);
+        self.phase = DirectorPhase::Spawning;
+        self.difficulty += self.difficulty_per_wave;
+    }
+}
+
+impl Default for SpawnDirector {
+    fn default() -> Self {
+        SpawnDirector::new(vec![
+            Wave::new(1.0, 3.0).with(EnemyKind::Eyeball, 5),
+            Wave::new(0.8, 5.0).with(EnemyKind::Eyeball, 6).with(EnemyKind::Runner, 3),
+            Wave::new(0.7, 5.0).with(EnemyKind::Eyeball, 6).with(EnemyKind::Runner, 4).with(EnemyKind::Brute, 1)
+                .with(EnemyKind::Spitter, 2),
+        ])
+    }
+}
+
+/// Asks the enemy plugin to bring an enemy of `kind` into the arena.
+#[derive(Clone, Debug)]
+pub struct SpawnEnemyEvent {
+    pub kind: EnemyKind,
+}
+
+#[derive(Clone, Debug)]
+pub struct WaveStarted {
+    pub wave: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct WaveCleared {
+    pub wave: u32,
+}
+
+fn reset_director(
+    mut director: ResMut<SpawnDirector>,
+){
+    director.restart();
+}
+
+/// Enemies asked for this frame are spawned by systems ordered after this one,
+/// so they are already counted as alive when the director next checks.
+#[allow(clippy::too_many_arguments)]
+pub fn run_director(
+    mut director: ResMut<SpawnDirector>,
+    registry: Res<EnemyRegistry>,
+    enemies: Query<(), With<Enemy>>,
+    telegraphs: Query<(), With<SpawnTelegraph>>,
+    pending: Res<PendingSpawns>,
+    time: Res<Time>,
+    mut spawn_events: EventWriter<SpawnEnemyEvent>,
+    mut started_events: EventWriter<WaveStarted>,
+    mut cleared_events: EventWriter<WaveCleared>,
+){
+    // enemies still looking for a spot or about to appear count as alive
+    let alive = (enemies.iter().count() + telegraphs.iter().count() + pending.0.len()) as u32;
+    director.difficulty += director.difficulty_per_minute * time.delta_seconds() / 60.0;
+    director.timer.tick(time.delta());
+
+    match director.phase {
+        DirectorPhase::Intermission => {
+            if director.timer.finished(){
+                let wave = match director.upcoming.take() {
+                    Some(wave) => wave,
+                    None => director.next_wave(&registry),
+                };
+                director.start_wave(wave);
+                started_events.send(WaveStarted{wave: director.wave});
+            }
+        }
+        DirectorPhase::Spawning => {
+            if director.timer.just_finished(){
+                if let Some(kind) = director.queue.pop(){
+                    spawn_events.send(SpawnEnemyEvent{kind});
+                }
+            }
+            if director.queue.is_empty(){
+                director.phase = DirectorPhase::Fighting;
+            }
+        }
+        DirectorPhase::Fighting => {
+            if alive == 0{
+                cleared_events.send(WaveCleared{wave: director.wave});
+                director.upcoming = Some(director.next_wave(&registry));
+                director.enter_intermission();
+            }
+        }
+    }
+
+    director.remaining = director.queue.len() as u32 + alive;
+}
+
+fn wave_sounds(
+    mut started_events: EventReader<WaveStarted>,
+    mut cleared_events: EventReader<WaveCleared>,
+    audio_controller: Res<AudioController>,
+    audio: Res<Audio>,
+){
+    for _ in started_events.iter(){
+        if let Some(sound) = audio_controller.get_handle("inferno"){
+            audio.play_with_settings(sound.handle, PlaybackSettings::ONCE.with_volume(0.4));
+        }
+    }
+    for _ in cleared_events.iter(){
+        if let Some(sound) = audio_controller.get_handle("bonk"){
+            audio.play_with_settings(sound.handle, PlaybackSettings::ONCE.with_volume(0.6));
+        }
+    }
+}
+
+
+pub struct DirectorPlugin;
+
+impl Plugin for DirectorPlugin{
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpawnDirector>()
+        .add_event::<SpawnEnemyEvent>()
+        .add_event::<WaveStarted>()
+        .add_event::<WaveCleared>()
+        .add_system(reset_director.in_schedule(OnEnter(AppState::InGame)).run_if(starting_run))
+        .add_systems((
+            run_director.in_set(OnUpdate(AppState::InGame)),
+            wave_sounds.in_set(OnUpdate(AppState::InGame)),
+        ));
+    }
+}
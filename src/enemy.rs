@@ -1,83 +1,611 @@
-use std::f32::consts::PI;
-use std::time::Duration;
 use rand::{Rng};
 
 use bevy::prelude::*;
-use bevy::time::common_conditions::on_timer;
-use bevy_rapier3d::prelude::{Velocity, RigidBody, CoefficientCombineRule, ActiveEvents, Friction, Collider};
+use bevy::utils::HashMap;
+use bevy_rapier3d::prelude::*;
 use crate::collision::{*, self};
-use crate::player::PlayerInfo;
-use crate::app_state::AppState;
+use crate::health::*;
+use crate::boss::Boss;
+use crate::pickup::{LootTable, PickupKind, PowerUp};
+use crate::navigation::NavAgent;
+use crate::steering::*;
+use crate::director::{run_director, SpawnEnemyEvent};
+use crate::player::{Player, PlayerInfo};
+use crate::bullet::{Bullet, Faction, ProjectilePool};
+use crate::app_state::{starting_run, AppState, StateScoped};
 
 
 #[derive(Component)]
 pub struct Enemy{
+    pub kind: EnemyKind,
+    pub contact_damage: i32,
+    pub score: u32,
 }
 
-#[derive(Resource, Clone)]
-pub struct EnemyMeshScene(pub Handle<Scene>);
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EnemyKind {
+    Eyeball,
+    Runner,
+    Brute,
+    Spitter,
+    Boss,
+}
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EnemyBehaviour {
+    /// Walks straight at the player.
+    Chase,
+    /// Keeps its distance and shoots at the player from within `range`.
+    Ranged {
+        range: f32,
+        /// Seconds between shots.
+        fire_interval: f32,
+        projectile_speed: f32,
+        damage: i32,
+    },
+    /// Runs through the phases and attacks of a `Boss`.
+    Boss,
+}
 
-fn move_enemy(
-    mut enemies: Query<(&mut Enemy, &mut Transform, &mut Velocity)>,
-    player_info: Res<PlayerInfo>,
+/// Everything needed to spawn one kind of enemy. Types only differ from
+/// `EnemyType::new` in a few fields, so the registry fills in the rest with
+/// struct update syntax.
+#[derive(Clone, Debug)]
+pub struct EnemyType {
+    pub kind: EnemyKind,
+    /// Name in the asset manifest of the scene drawn for this enemy.
+    pub scene: String,
+    /// Scale applied to the scene, the collider is sized separately.
+    pub scale: f32,
+    pub half_extents: Vec3,
+    pub speed: f32,
+    pub health: i32,
+    pub contact_damage: i32,
+    pub score: u32,
+    pub behaviour: EnemyBehaviour,
+    pub steering: SteeringWeights,
+    /// Rolled for drops when an enemy of this type dies.
+    pub loot: LootTable,
+    /// Relative chance of being picked by `EnemyRegistry::choose`.
+    pub spawn_weight: u32,
+}
+
+impl EnemyType {
+    /// A slow, one hit chaser drawn with `scene` at `scale`, the collider
+    /// sized to match.
+    pub fn new(kind: EnemyKind, scene: &str, scale: f32) -> Self {
+        EnemyType {
+            kind,
+            scene: scene.to_string(),
+            scale,
+            half_extents: Vec3::splat(0.5 * scale),
+            speed: 1.0,
+            health: 1,
+            contact_damage: 1,
+            score: 10,
+            behaviour: EnemyBehaviour::Chase,
+            steering: SteeringWeights::default(),
+            loot: LootTable::standard(1),
+            spawn_weight: 1,
+        }
+    }
+}
+
+/// Every enemy type the game knows how to spawn.
+#[derive(Resource, Clone, Debug)]
+pub struct EnemyRegistry{
+    pub types: Vec<EnemyType>,
+}
+
+impl EnemyRegistry {
+    /// Adds a type, replacing any earlier registration of the same kind.
+    pub fn register(&mut self, enemy_type: EnemyType) -> &mut Self {
+        self.types.retain(|registered| registered.kind != enemy_type.kind);
+        self.types.push(enemy_type);
+        self
+    }
+
+    pub fn get(&self, kind: EnemyKind) -> Option<&EnemyType> {
+        self.types.iter().find(|enemy_type| enemy_type.kind == kind)
+    }
+
+    /// Picks a type at random, weighted by `spawn_weight`.
+    pub fn choose<R: Rng>(&self, rng: &mut R) -> Option<&EnemyType> {
+        let total: u32 = self.types.iter().map(|enemy_type| enemy_type.spawn_weight).sum();
+        if total == 0{
+            return None;
+        }
+        let mut roll = rng.gen_range(0, total);
+        for enemy_type in self.types.iter(){
+            if roll < enemy_type.spawn_weight{
+                return Some(enemy_type);
+            }
+            roll -= enemy_type.spawn_weight;
+        }
+        None
+    }
+}
+
+impl Default for EnemyRegistry {
+    fn default() -> Self {
+        let mut registry = EnemyRegistry{ types: Vec::new() };
+        registry
+            .register(EnemyType{
+                spawn_weight: 6,
+                ..EnemyType::new(EnemyKind::Eyeball, "eyeball", 1.0)
+            })
+            .register(EnemyType{
+                speed: 2.5,
+                steering: SteeringWeights{
+                    separation: 1.0,
+                    wander: 0.6,
+                    ..default()
+                },
+                score: 15,
+                spawn_weight: 3,
+                ..EnemyType::new(EnemyKind::Runner, "eyeball", 0.6)
+            })
+            .register(EnemyType{
+                speed: 0.6,
+                health: 5,
+                contact_damage: 2,
+                loot: LootTable::standard(4).with(PickupKind::Health(3), 0.25),
+                steering: SteeringWeights{
+                    separation: 0.5,
+                    wall_avoidance: 1.0,
+                    wander: 0.0,
+                    separation_radius: 3.0,
+                    ..default()
+                },
+                score: 40,
+                ..EnemyType::new(EnemyKind::Brute, "eyeball", 1.6)
+            })
+            .register(EnemyType{
+                speed: 1.5,
+                health: 2,
+                behaviour: EnemyBehaviour::Ranged {
+                    range: 14.0,
+                    fire_interval: 2.0,
+                    projectile_speed: 8.0,
+                    damage: 1,
+                },
+                steering: SteeringWeights{
+                    seek: 0.0,
+                    orbit: 1.0,
+                    orbit_range: 10.0,
+                    ..default()
+                },
+                score: 25,
+                spawn_weight: 2,
+                ..EnemyType::new(EnemyKind::Spitter, "eyeball", 0.8)
+            })
+            // only ever brought in by the director's boss waves
+            .register(EnemyType{
+                health: 60,
+                contact_damage: 3,
+                behaviour: EnemyBehaviour::Boss,
+                loot: LootTable::default()
+                    .with(PickupKind::Experience(50), 1.0)
+                    .with(PickupKind::Health(5), 1.0)
+                    .with(PickupKind::Ammo(100), 1.0)
+                    .with(PickupKind::PowerUp(PowerUp::Damage), 0.5)
+                    .with(PickupKind::PowerUp(PowerUp::Shield), 0.5),
+                steering: SteeringWeights{
+                    separation: 0.0,
+                    wander: 0.0,
+                    lookahead: 3.0,
+                    ..default()
+                },
+                score: 500,
+                spawn_weight: 0,
+                ..EnemyType::new(EnemyKind::Boss, "eyeball", 3.0)
+            });
+        registry
+    }
+}
+
+/// The loaded scene of every registered enemy type.
+#[derive(Resource, Clone, Default)]
+pub struct EnemyMeshScenes(pub HashMap<EnemyKind, Handle<Scene>>);
+
+
+
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    enemy_type: &EnemyType,
+    scenes: &EnemyMeshScenes,
+    position: Vec3,
+) -> Entity {
+    let mut enemy = commands.spawn((
+        RigidBody::KinematicVelocityBased,
+        StateScoped(AppState::InGame),
+        // sit on the ground whatever the size
+        SpatialBundle::from_transform(Transform::from_translation(
+            Vec3::new(position.x, enemy_type.half_extents.y, position.z)
+        )),
+        collision::Collidable{kind: collision::CollidableKind::Enemy},
+        ActiveEvents::COLLISION_EVENTS,
+        Friction{
+            coefficient:0.0,
+            combine_rule: CoefficientCombineRule::Min
+        },
+        Enemy{
+            kind: enemy_type.kind,
+            contact_damage: enemy_type.contact_damage,
+            score: enemy_type.score,
+        },
+        Health::new(enemy_type.health),
+        Steering::new(enemy_type.steering, enemy_type.speed),
+        NavAgent::default(),
+        Velocity::default(),
+        Collider::cuboid(enemy_type.half_extents.x, enemy_type.half_extents.y, enemy_type.half_extents.z),
+    ));
+    if let EnemyBehaviour::Ranged { range, fire_interval, projectile_speed, damage } = enemy_type.behaviour{
+        enemy.insert(RangedAttack{
+            range,
+            projectile_speed,
+            damage,
+            cooldown: Timer::from_seconds(fire_interval, TimerMode::Repeating),
+        });
+    }
+    if enemy_type.behaviour == EnemyBehaviour::Boss{
+        enemy.insert(Boss::default());
+    }
+    // the scene is a child so scaling it doesn't also scale the collider
+    if let Some(scene) = scenes.0.get(&enemy_type.kind){
+        enemy.with_children(|children| {
+            children.spawn(SceneBundle{
+                scene: scene.clone(),
+                transform: Transform::from_scale(Vec3::splat(enemy_type.scale)),
+                ..default()
+            });
+        });
+    }
+    enemy.id()
+}
+
+#[derive(Clone, Debug)]
+pub enum SpawnPlacement {
+    /// Anywhere on the arena floor.
+    Anywhere,
+    /// Within `depth` of the arena's walls.
+    Edges { depth: f32 },
+    /// Only at these designated points.
+    Points(Vec<Vec3>),
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct SpawnSettings {
+    /// Half the width of the square of floor enemies may appear on.
+    pub area_half_extent: f32,
+    /// Enemies never appear closer than this to the player.
+    pub min_player_distance: f32,
+    /// Candidate points tried per enemy each frame before waiting for the next one.
+    pub attempts: u32,
+    pub placement: SpawnPlacement,
+    /// Seconds a marker is shown before the enemy appears, `None` to spawn at once.
+    pub telegraph: Option<f32>,
+}
+
+impl SpawnSettings {
+    /// Why no spawn point could ever be picked with these settings, if so.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.area_half_extent <= 0.0{
+            return Err("area_half_extent must be positive");
+        }
+        match &self.placement {
+            SpawnPlacement::Edges { depth } if *depth <= 0.0 => Err("edge depth must be positive"),
+            SpawnPlacement::Points(points) if points.is_empty() => Err("no spawn points given"),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for SpawnSettings {
+    fn default() -> Self {
+        SpawnSettings {
+            area_half_extent: 23.0,
+            min_player_distance: 8.0,
+            attempts: 10,
+            placement: SpawnPlacement::Anywhere,
+            telegraph: Some(1.0),
+        }
+    }
+}
+
+/// Enemies the director asked for that haven't found a free spot yet.
+#[derive(Resource, Default)]
+pub struct PendingSpawns(pub Vec<EnemyKind>);
+
+/// Warning marker on the floor where an enemy is about to appear.
+#[derive(Component)]
+pub struct SpawnTelegraph {
+    pub kind: EnemyKind,
+    pub timer: Timer,
+}
+
+#[derive(Resource)]
+pub struct SpawnTelegraphAssets {
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<StandardMaterial>,
+}
+
+fn create_telegraph_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
 ){
-    for (_, mut transform, mut velocity) in enemies.iter_mut() {
-        let vec_between = transform.translation - player_info.position;
-        let angle = vec_between.x.atan2(vec_between.z);
-        transform.rotation = Quat::from_axis_angle(Vec3::Y, angle+PI);
-        let forward = transform.forward();
-        velocity.linvel = -forward;
+    commands.insert_resource(SpawnTelegraphAssets{
+        mesh: meshes.add(Mesh::from(shape::Cylinder{radius: 1.0, height: 0.02, ..default()})),
+        material: materials.add(StandardMaterial{
+            base_color: Color::rgba(1.0, 0.1, 0.1, 0.6),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        }),
+    });
+}
+
+fn candidate_point<R: Rng>(settings: &SpawnSettings, rng: &mut R) -> Option<Vec3> {
+    let extent = settings.area_half_extent;
+    match &settings.placement {
+        SpawnPlacement::Anywhere => {
+            Some(Vec3::new(rng.gen_range(-extent, extent), 0.0, rng.gen_range(-extent, extent)))
+        }
+        SpawnPlacement::Edges { depth } => {
+            let along = rng.gen_range(-extent, extent);
+            let across = extent - rng.gen_range(0.0, depth.min(extent));
+            let side = if rng.gen() { 1.0 } else { -1.0 };
+            Some(if rng.gen() {
+                Vec3::new(along, 0.0, across * side)
+            } else {
+                Vec3::new(across * side, 0.0, along)
+            })
+        }
+        SpawnPlacement::Points(points) => {
+            if points.is_empty(){
+                return None;
+            }
+            Some(points[rng.gen_range(0, points.len())])
+        }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn spawn_enemies(
     mut commands: Commands,
-    enemy_mesh: ResMut<EnemyMeshScene>,
+    mut spawn_events: EventReader<SpawnEnemyEvent>,
+    mut pending: ResMut<PendingSpawns>,
+    registry: Res<EnemyRegistry>,
+    scenes: Res<EnemyMeshScenes>,
+    settings: Res<SpawnSettings>,
+    telegraph_assets: Res<SpawnTelegraphAssets>,
+    telegraphs: Query<&Transform, With<SpawnTelegraph>>,
+    triggers: Query<&TriggerVolume>,
+    player_info: Res<PlayerInfo>,
+    context: Res<RapierContext>,
 ){
+    pending.0.extend(spawn_events.iter().map(|spawn| spawn.kind));
+    // keep the requests until the settings are fixed
+    if let Err(problem) = settings.validate(){
+        if settings.is_changed(){
+            warn!("not spawning enemies, invalid spawn settings: {}", problem);
+        }
+        return;
+    }
+    if pending.0.is_empty(){
+        return;
+    }
+
     let mut rng = rand::thread_rng();
-    let x = rng.gen_range(-23,23);
-    let z = rng.gen_range(-23,23);
-    commands
-        .spawn(RigidBody::KinematicVelocityBased)
-        .insert(SpatialBundle{..default()})
-        .insert(SceneBundle{
-            scene: enemy_mesh.0.clone(),
-            transform: Transform::from_xyz(x as f32, 0.5, z as f32),
-            ..default()
-        })
-        /*.insert(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Box::new(1.0, 1.0, 1.0))),
-            transform: Transform::from_xyz(x as f32, 0.5, z as f32),
-            material: materials.add(Color::rgb(0.0, 1.0, 0.0).into()),
-            ..default()
-        })*/
-        .insert(collision::Collidable{kind: collision::CollidableKind::Enemy})
-        .insert(ActiveEvents::COLLISION_EVENTS)
-        .insert(Friction{
-            coefficient:0.0,
-            combine_rule: CoefficientCombineRule::Min
-        })
-        .insert(Enemy{})
-        .insert(Velocity::default())
-        .insert(Collider::cuboid(0.5, 0.5, 0.5));
+    // spots taken by markers and by enemies placed this frame, which the
+    // physics world doesn't know about yet
+    let mut claimed: Vec<(Vec3, f32)> = telegraphs.iter()
+        .map(|transform| (transform.translation, transform.scale.x))
+        .collect();
+    // only walls, bodies and spawn blocking triggers get in the way
+    let is_obstacle = |entity: Entity| triggers.get(entity).map_or(true, |trigger| trigger.kind == TriggerKind::SpawnBlocker);
+    let filter = QueryFilter::from(CollisionGroups::new(
+        Group::ALL,
+        CollidableKind::Wall.group() | CollidableKind::Enemy.group() | CollidableKind::Player.group() | CollidableKind::Trigger.group(),
+    )).predicate(&is_obstacle);
+
+    let mut still_pending = Vec::new();
+    for kind in pending.0.drain(..){
+        let Some(enemy_type) = registry.get(kind) else{
+            warn!("no enemy type registered for {:?}", kind);
+            continue;
+        };
+        let half_extents = enemy_type.half_extents;
+        let radius = half_extents.x.max(half_extents.z);
+        let shape = Collider::cuboid(half_extents.x, half_extents.y, half_extents.z);
+
+        let point = (0..settings.attempts)
+            .filter_map(|_| candidate_point(&settings, &mut rng))
+            .find(|point| {
+                let flat_player = Vec3::new(player_info.position.x, 0.0, player_info.position.z);
+                point.distance(flat_player) >= settings.min_player_distance
+                    && claimed.iter().all(|(other, other_radius)| point.distance(*other) > radius + other_radius)
+                    && context.intersection_with_shape(
+                        Vec3::new(point.x, half_extents.y, point.z), Quat::IDENTITY, &shape, filter
+                    ).is_none()
+            });
+        let Some(point) = point else{
+            still_pending.push(kind);
+            continue;
+        };
+        claimed.push((point, radius));
+
+        match settings.telegraph {
+            Some(delay) => {
+                commands.spawn((
+                    SpawnTelegraph{kind, timer: Timer::from_seconds(delay, TimerMode::Once)},
+                    StateScoped(AppState::InGame),
+                    PbrBundle {
+                        mesh: telegraph_assets.mesh.clone(),
+                        material: telegraph_assets.material.clone(),
+                        transform: Transform::from_translation(point + Vec3::Y * 0.11)
+                            .with_scale(Vec3::new(radius, 1.0, radius)),
+                        ..default()
+                    },
+                ));
+            }
+            None => {
+                spawn_enemy(&mut commands, enemy_type, &scenes, point);
+            }
+        }
+    }
+    pending.0 = still_pending;
 }
 
-fn enemy_collision(
-    mut enemies: Query<(Entity, &CollisionTag), With<Enemy>>,
-    mut commands: Commands
-    //player_info: Res<PlayerInfo>,
+/// Spawns the enemy under every finished marker. If the player has walked
+/// too close in the meantime the enemy goes back to find another spot.
+#[allow(clippy::too_many_arguments)]
+fn resolve_telegraphs(
+    mut commands: Commands,
+    mut telegraphs: Query<(Entity, &mut SpawnTelegraph, &mut Transform)>,
+    mut pending: ResMut<PendingSpawns>,
+    registry: Res<EnemyRegistry>,
+    scenes: Res<EnemyMeshScenes>,
+    settings: Res<SpawnSettings>,
+    player_info: Res<PlayerInfo>,
+    time: Res<Time>,
+){
+    let flat_player = Vec3::new(player_info.position.x, 0.0, player_info.position.z);
+    for (entity, mut telegraph, mut transform) in telegraphs.iter_mut(){
+        telegraph.timer.tick(time.delta());
+        // pulse so it reads as a warning rather than decoration
+        transform.scale.y = 1.0 + (telegraph.timer.elapsed_secs() * 12.0).sin().abs() * 4.0;
+        if !telegraph.timer.finished(){
+            continue;
+        }
+        let point = transform.translation;
+        if Vec3::new(point.x, 0.0, point.z).distance(flat_player) < settings.min_player_distance{
+            pending.0.push(telegraph.kind);
+        }else if let Some(enemy_type) = registry.get(telegraph.kind){
+            spawn_enemy(&mut commands, enemy_type, &scenes, point);
+        }
+        commands.entity(entity).despawn();
+    }
+}
+
+fn clear_pending_spawns(
+    mut pending: ResMut<PendingSpawns>,
 ){
+    pending.0.clear();
+}
 
-    for (entity, tag) in enemies.iter_mut(){
-        println!("{:?}", tag.other.kind);
-        match tag.other.kind {
-            CollidableKind::Bullet => {
-                println!("{:?}", entity);
-                commands.entity(entity).despawn_recursive();
-                //commands.entity(entity).despawn();
-            }
-            _ => {}
+pub struct EnemyTouchesPlayer;
+
+impl CollisionPair for EnemyTouchesPlayer{
+    const THIS: CollidableKind = CollidableKind::Enemy;
+    const OTHER: CollidableKind = CollidableKind::Player;
+}
+
+fn enemy_contact_damage(
+    enemies: Query<&Enemy>,
+    mut player_contacts: EventReader<PairCollisionEvent<EnemyTouchesPlayer>>,
+    mut damage_events: EventWriter<DamageEvent>,
+){
+    for contact in player_contacts.iter(){
+        if contact.phase == ContactPhase::Stopped{
+            continue;
+        }
+        let Ok(enemy) = enemies.get(contact.this.entity) else{
+            continue;
+        };
+        damage_events.send(DamageEvent{
+            target: contact.other.entity,
+            source: Some(contact.this.entity),
+            amount: enemy.contact_damage,
+            kind: DamageKind::Contact,
+        });
+    }
+}
+
+/// Shoots at the player whenever its cooldown comes round and the player is
+/// within range.
+#[derive(Component, Clone, Debug)]
+pub struct RangedAttack {
+    pub range: f32,
+    pub projectile_speed: f32,
+    pub damage: i32,
+    pub cooldown: Timer,
+}
+
+/// Where to aim from `shooter` so a projectile of `speed` meets a target
+/// moving at a constant `target_velocity`. Aims straight at the target when
+/// it can't be caught.
+pub fn lead_target(shooter: Vec3, target: Vec3, target_velocity: Vec3, speed: f32) -> Vec3 {
+    let offset = target - shooter;
+    // |offset + velocity * t| = speed * t, solved for the soonest t > 0
+    let a = target_velocity.length_squared() - speed * speed;
+    let b = 2.0 * offset.dot(target_velocity);
+    let c = offset.length_squared();
+    let time = if a.abs() < f32::EPSILON {
+        if b.abs() < f32::EPSILON { None } else { Some(-c / b) }
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0{
+            None
+        } else {
+            let root = discriminant.sqrt();
+            [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+                .into_iter()
+                .filter(|time| *time > 0.0)
+                .reduce(f32::min)
+        }
+    };
+    match time {
+        Some(time) if time > 0.0 => target + target_velocity * time,
+        _ => target,
+    }
+}
+
+fn enemy_fire(
+    mut enemies: Query<(Entity, &Transform, &mut RangedAttack)>,
+    players: Query<(&Transform, &Velocity), With<Player>>,
+    mut pool: ResMut<ProjectilePool>,
+    mut commands: Commands,
+    time: Res<Time>,
+){
+    let Ok((player_transform, player_velocity)) = players.get_single() else{
+        return;
+    };
+    let target = player_transform.translation;
+    for (entity, transform, mut attack) in enemies.iter_mut(){
+        attack.cooldown.tick(time.delta());
+        if !attack.cooldown.just_finished(){
+            continue;
+        }
+        // fire level with the player so the shot doesn't go under or over it
+        let muzzle = Vec3::new(transform.translation.x, target.y, transform.translation.z);
+        if muzzle.distance(target) > attack.range{
+            continue;
+        }
+        let aim = lead_target(muzzle, target, player_velocity.linvel, attack.projectile_speed);
+        let direction = Vec3::new(aim.x - muzzle.x, 0.0, aim.z - muzzle.z).normalize_or_zero();
+        if direction == Vec3::ZERO{
+            continue;
+        }
+        pool.spawn(
+            &mut commands,
+            muzzle + direction * 0.75,
+            direction * attack.projectile_speed,
+            Bullet::new(attack.damage, 2.0 * attack.range / attack.projectile_speed, attack.range * 1.5)
+                .fired_by(entity, Faction::Enemy),
+        );
+    }
+}
+
+pub fn enemy_death(
+    enemies: Query<Entity, With<Enemy>>,
+    mut death_events: EventReader<DeathEvent>,
+    mut commands: Commands
+){
+    for death in death_events.iter(){
+        if enemies.contains(death.entity){
+            commands.entity(death.entity).despawn_recursive();
         }
     }
 }
@@ -89,10 +617,19 @@ pub struct EnemyPlugin;
 
 impl Plugin for EnemyPlugin{
     fn build(&self, app: &mut App) {
-        app.add_systems((
-            spawn_enemies.run_if(in_state(AppState::InGame)).run_if(on_timer(Duration::from_secs(1))),
-            move_enemy.in_set(OnUpdate(AppState::InGame)),
-            enemy_collision.in_set(OnUpdate(AppState::InGame)),
+        app.init_resource::<EnemyRegistry>()
+        .init_resource::<EnemyMeshScenes>()
+        .init_resource::<SpawnSettings>()
+        .init_resource::<PendingSpawns>()
+        .add_startup_system(create_telegraph_assets)
+        .add_system(clear_pending_spawns.in_schedule(OnEnter(AppState::InGame)).run_if(starting_run))
+        .add_collision_pair::<EnemyTouchesPlayer>()
+        .add_systems((
+            spawn_enemies.in_set(OnUpdate(AppState::InGame)).after(run_director),
+            resolve_telegraphs.in_set(OnUpdate(AppState::InGame)).before(spawn_enemies),
+            enemy_contact_damage.in_set(OnUpdate(AppState::InGame)),
+            enemy_death.in_set(OnUpdate(AppState::InGame)).after(apply_damage),
+            enemy_fire.in_set(OnUpdate(AppState::InGame)),
         ));
     }
 }
\ No newline at end of file
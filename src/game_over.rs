@@ -0,0 +1,102 @@
+use bevy::prelude::*;
+
+use crate::app_state::AppState;
+use crate::director::SpawnDirector;
+use crate::highscore::HighScores;
+use crate::manifest::FontHandles;
+use crate::overlay::*;
+use crate::stats::RunStats;
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameOverButton {
+    Retry,
+    MainMenu,
+}
+
+fn results_text(stats: &RunStats, wave: u32, high_scores: &HighScores) -> String {
+    let seconds = stats.time_survived as u32;
+    let mut kills: Vec<String> = stats.kills.iter()
+        .map(|(kind, count)| format!("{:?} {}", kind, count))
+        .collect();
+    kills.sort();
+    let mut text = format!(
+        "{}\nScore {}\nWave {}   Time {}:{:02}\nKills {} ({})\nAccuracy {:.0}%   Best combo {}\nDamage taken {}",
+        stats.cause_of_death.as_deref().unwrap_or("Game over"),
+        stats.score,
+        wave,
+        seconds / 60,
+        seconds % 60,
+        stats.total_kills(),
+        if kills.is_empty() { "none".to_string() } else { kills.join(", ") },
+        stats.accuracy() * 100.0,
+        stats.highest_combo,
+        stats.damage_taken,
+    );
+    if let Some(rank) = high_scores.last_rank{
+        text += &format!("\nNew high score, #{}!", rank + 1);
+    }
+    text
+}
+
+fn setup(
+    mut commands: Commands,
+    fonts: Res<FontHandles>,
+    stats: Res<RunStats>,
+    director: Res<SpawnDirector>,
+    high_scores: Res<HighScores>,
+){
+    let font = fonts.get("main");
+    spawn_overlay(&mut commands, AppState::GameOver, 0.7, |parent| {
+        parent.spawn(TextBundle::from_section(
+            "GAME OVER",
+            TextStyle{
+                font: font.clone(),
+                font_size: 80.0,
+                color: Color::RED,
+            },
+        ));
+        parent.spawn(TextBundle::from_section(
+            results_text(&stats, director.wave, &high_scores),
+            TextStyle{
+                font: font.clone(),
+                font_size: 28.0,
+                color: Color::WHITE,
+            },
+        ).with_text_alignment(TextAlignment::Center));
+        spawn_button(parent, GameOverButton::Retry, "Retry", &font);
+        spawn_button(parent, GameOverButton::MainMenu, "Main Menu", &font);
+    });
+}
+
+fn game_over_buttons(
+    mut buttons: Query<(&Interaction, &GameOverButton, &mut BackgroundColor), Changed<Interaction>>,
+    keys: Res<Input<KeyCode>>,
+    mut next_state: ResMut<NextState<AppState>>,
+){
+    let mut choice = clicked_button(&mut buttons);
+    if keys.just_pressed(KeyCode::R) || keys.just_pressed(KeyCode::Return){
+        choice = Some(GameOverButton::Retry);
+    }
+    if keys.just_pressed(KeyCode::Escape) || keys.just_pressed(KeyCode::M){
+        choice = Some(GameOverButton::MainMenu);
+    }
+    match choice {
+        Some(GameOverButton::Retry) => next_state.set(AppState::InGame),
+        Some(GameOverButton::MainMenu) => next_state.set(AppState::MainMenu),
+        None => {}
+    }
+}
+
+
+pub struct GameOverPlugin;
+
+impl Plugin for GameOverPlugin{
+    fn build(&self, app: &mut App) {
+        app.add_systems((
+            freeze_simulation.in_schedule(OnEnter(AppState::GameOver)),
+            setup.in_schedule(OnEnter(AppState::GameOver)),
+        ))
+        .add_system(resume_simulation.in_schedule(OnExit(AppState::GameOver)))
+        .add_system(game_over_buttons.in_set(OnUpdate(AppState::GameOver)));
+    }
+}
@@ -0,0 +1,174 @@
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::app_state::AppState;
+use crate::buff::Buffs;
+use crate::collision::*;
+use crate::pickup::PowerUp;
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Health {
+    pub current: i32,
+    pub max: i32,
+}
+
+impl Health {
+    pub fn new(max: i32) -> Self {
+        Health { current: max, max }
+    }
+
+    pub fn fraction(&self) -> f32 {
+        (self.current as f32 / self.max as f32).clamp(0.0, 1.0)
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0
+    }
+
+    pub fn heal(&mut self, amount: i32) {
+        self.current = (self.current + amount).min(self.max);
+    }
+}
+
+/// Invulnerability window started every time the entity takes damage.
+/// While the timer is running, incoming damage is ignored.
+#[derive(Component)]
+pub struct DamageCooldown{
+    pub timer: Timer,
+}
+
+impl DamageCooldown {
+    /// A cooldown that starts out inactive.
+    pub fn new(window: Duration) -> Self {
+        let mut timer = Timer::new(window, TimerMode::Once);
+        timer.pause();
+        DamageCooldown { timer }
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.timer.paused()
+    }
+
+    fn start(&mut self) {
+        self.timer.reset();
+        self.timer.unpause();
+    }
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum DamageKind {
+    Contact,
+    Projectile,
+    Hazard,
+}
+
+#[derive(Clone, Debug)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub source: Option<Entity>,
+    pub amount: i32,
+    pub kind: DamageKind,
+}
+
+/// Sent once when an entity's health reaches zero. Nothing is despawned here,
+/// each plugin decides what dying means for its own entities.
+#[derive(Clone, Debug)]
+pub struct DeathEvent {
+    pub entity: Entity,
+    pub source: Option<Entity>,
+    pub kind: DamageKind,
+    pub position: Vec3,
+}
+
+/// Deals `amount` to everything inside the attached trigger volume every `timer` tick.
+#[derive(Component)]
+pub struct Hazard {
+    pub amount: i32,
+    pub timer: Timer,
+}
+
+fn tick_damage_cooldown(
+    mut damage_cooldowns: Query<&mut DamageCooldown>,
+    time: Res<Time>
+){
+    for mut damage_cooldown in damage_cooldowns.iter_mut(){
+        damage_cooldown.timer.tick(time.delta());
+        if damage_cooldown.timer.just_finished(){
+            damage_cooldown.timer.pause();
+        }
+    }
+}
+
+fn hazard_damage(
+    mut hazards: Query<(Entity, &mut Hazard, &TriggerVolume)>,
+    mut damage_events: EventWriter<DamageEvent>,
+    time: Res<Time>,
+){
+    for (entity, mut hazard, trigger) in hazards.iter_mut(){
+        hazard.timer.tick(time.delta());
+        if !hazard.timer.just_finished(){
+            continue;
+        }
+        for occupant in trigger.occupants.iter(){
+            damage_events.send(DamageEvent{
+                target: occupant.entity,
+                source: Some(entity),
+                amount: hazard.amount,
+                kind: DamageKind::Hazard,
+            });
+        }
+    }
+}
+
+pub fn apply_damage(
+    mut damage_events: EventReader<DamageEvent>,
+    mut targets: Query<(&mut Health, Option<&mut DamageCooldown>, Option<&mut Buffs>, &GlobalTransform)>,
+    mut death_events: EventWriter<DeathEvent>,
+){
+    for damage in damage_events.iter(){
+        let Ok((mut health, cooldown, buffs, transform)) = targets.get_mut(damage.target) else{
+            continue;
+        };
+        // already dead, waiting to be cleaned up by its owner
+        if health.is_dead(){
+            continue;
+        }
+        if let Some(mut cooldown) = cooldown{
+            if cooldown.is_active(){
+                continue;
+            }
+            cooldown.start();
+        }
+        // a shield stack soaks up the whole hit
+        if let Some(mut buffs) = buffs{
+            if buffs.consume_stack(PowerUp::Shield){
+                continue;
+            }
+        }
+
+        health.current = (health.current - damage.amount).min(health.max);
+        if health.is_dead(){
+            death_events.send(DeathEvent{
+                entity: damage.target,
+                source: damage.source,
+                kind: damage.kind,
+                position: transform.translation(),
+            });
+        }
+    }
+}
+
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin{
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>()
+        .add_event::<DeathEvent>()
+        .add_systems((
+            tick_damage_cooldown.in_set(OnUpdate(AppState::InGame)),
+            hazard_damage.in_set(OnUpdate(AppState::InGame)),
+            apply_damage.in_set(OnUpdate(AppState::InGame)).after(hazard_damage),
+        ));
+    }
+}
@@ -0,0 +1,363 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::app_state::{starting_run, AppState};
+use crate::director::SpawnDirector;
+use crate::health::{apply_damage, DeathEvent};
+use crate::player::Player;
+use crate::stats::RunStats;
+
+/// Set to use this file instead of the one in the platform data directory.
+pub const HIGH_SCORE_PATH_VAR: &str = "HONORS_HIGH_SCORE_PATH";
+/// Longest name the table keeps, so it fits its column on the menu.
+pub const MAX_NAME_LENGTH: usize = 12;
+/// Used when no usable name is found.
+pub const DEFAULT_NAME: &str = "Player";
+
+/// `name` without control characters or surrounding whitespace, cut to
+/// `MAX_NAME_LENGTH`, or `None` if nothing is left.
+fn clean_name(name: &str) -> Option<String> {
+    let name: String = name.chars()
+        .filter(|character| !character.is_control())
+        .collect::<String>()
+        .trim()
+        .chars()
+        .take(MAX_NAME_LENGTH)
+        .collect();
+    let name = name.trim_end();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// `name` cleaned up for the table, or `DEFAULT_NAME` if nothing is left.
+pub fn sanitize_name(name: &str) -> String {
+    clean_name(name).unwrap_or_else(|| DEFAULT_NAME.to_string())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HighScoreEntry {
+    pub name: String,
+    pub score: u32,
+    pub wave: u32,
+    /// Seconds the run lasted.
+    pub duration: f32,
+    /// When the run ended, in seconds since the unix epoch.
+    pub date: u64,
+}
+
+impl HighScoreEntry {
+    /// The run's date as `YYYY-MM-DD`, in UTC.
+    pub fn date_string(&self) -> String {
+        // days since the epoch to a civil date, after Howard Hinnant's algorithm
+        let days = (self.date / 86_400) as i64 + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+        let year = year_of_era + era * 400 + (month <= 2) as i64;
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+
+    fn is_valid(&self) -> bool {
+        self.duration.is_finite() && self.duration >= 0.0
+    }
+}
+
+/// Best runs, highest score first.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct HighScoreTable {
+    pub entries: Vec<HighScoreEntry>,
+}
+
+impl HighScoreTable {
+    /// Adds the run if it makes the top `capacity`, returning its rank from 0.
+    pub fn insert(&mut self, mut entry: HighScoreEntry, capacity: usize) -> Option<usize> {
+        entry.name = sanitize_name(&entry.name);
+        // ties go below the runs that got there first
+        let rank = self.entries.iter().position(|existing| existing.score < entry.score).unwrap_or(self.entries.len());
+        if rank >= capacity{
+            return None;
+        }
+        self.entries.insert(rank, entry);
+        self.entries.truncate(capacity);
+        Some(rank)
+    }
+
+    /// Drops broken entries and puts the rest back in order.
+    fn sanitize(&mut self, capacity: usize) {
+        self.entries.retain(HighScoreEntry::is_valid);
+        for entry in self.entries.iter_mut(){
+            entry.name = sanitize_name(&entry.name);
+        }
+        self.entries.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+        self.entries.truncate(capacity);
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct HighScores {
+    pub table: HighScoreTable,
+    /// Where the table is saved, `None` to keep it in memory only.
+    pub path: Option<PathBuf>,
+    pub capacity: usize,
+    /// Rank of the run that just ended, if it made the table.
+    pub last_rank: Option<usize>,
+}
+
+impl HighScores {
+    /// The table stored at `path`. A missing file is an empty table, and one
+    /// that can't be read is moved aside so it isn't overwritten blindly.
+    pub fn with_path(path: impl Into<PathBuf>, capacity: usize) -> Self {
+        let path = path.into();
+        let table = match fs::read_to_string(&path) {
+            Ok(contents) => match ron::from_str::<HighScoreTable>(&contents) {
+                Ok(mut table) => {
+                    table.sanitize(capacity);
+                    table
+                }
+                Err(error) => {
+                    warn!("high score file {} is corrupt ({}), starting a new one", path.display(), error);
+                    let backup = path.with_extension("ron.corrupt");
+                    if let Err(error) = fs::rename(&path, &backup){
+                        warn!("could not move corrupt high score file aside: {}", error);
+                    }
+                    HighScoreTable::default()
+                }
+            },
+            Err(error) if error.kind() == io::ErrorKind::NotFound => HighScoreTable::default(),
+            Err(error) => {
+                warn!("could not read high score file {}: {}", path.display(), error);
+                HighScoreTable::default()
+            }
+        };
+        HighScores { table, path: Some(path), capacity, last_rank: None }
+    }
+
+    /// Scores kept in memory only, never saved.
+    pub fn in_memory(capacity: usize) -> Self {
+        HighScores { table: HighScoreTable::default(), path: None, capacity, last_rank: None }
+    }
+
+    pub fn record(&mut self, entry: HighScoreEntry) -> Option<usize> {
+        self.last_rank = self.table.insert(entry, self.capacity);
+        self.last_rank
+    }
+
+    /// Writes the table out, going through a temporary file so a crash halfway
+    /// never leaves a truncated table behind.
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = self.path.as_ref() else{
+            return Ok(());
+        };
+        if let Some(parent) = path.parent(){
+            fs::create_dir_all(parent)?;
+        }
+        let contents = ron::ser::to_string_pretty(&self.table, ron::ser::PrettyConfig::default())
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        let temporary = path.with_extension("ron.tmp");
+        fs::write(&temporary, contents)?;
+        fs::rename(&temporary, path)
+    }
+}
+
+impl Default for HighScores {
+    fn default() -> Self {
+        match high_score_path() {
+            Some(path) => HighScores::with_path(path, 10),
+            None => HighScores::in_memory(10),
+        }
+    }
+}
+
+/// `HONORS_HIGH_SCORE_PATH` if set, otherwise a file in the platform's data directory.
+pub fn high_score_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(HIGH_SCORE_PATH_VAR){
+        return Some(PathBuf::from(path));
+    }
+    data_dir().map(|dir| dir.join("honors_conference_sp23").join("highscores.ron"))
+}
+
+fn data_dir() -> Option<PathBuf> {
+    let env_path = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    if cfg!(target_os = "windows") {
+        env_path("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_path("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env_path("XDG_DATA_HOME").or_else(|| env_path("HOME").map(|home| home.join(".local").join("share")))
+    }
+}
+
+/// Name the runs are saved under.
+#[derive(Resource, Clone, Debug)]
+pub struct PlayerName(pub String);
+
+impl Default for PlayerName {
+    fn default() -> Self {
+        // USER on unix, USERNAME on windows
+        let name = ["USER", "USERNAME"].iter()
+            .find_map(|var| std::env::var(var).ok().and_then(|name| clean_name(&name)))
+            .unwrap_or_else(|| DEFAULT_NAME.to_string());
+        PlayerName(name)
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs())
+}
+
+fn record_high_score(
+    mut death_events: EventReader<DeathEvent>,
+    players: Query<(), With<Player>>,
+    stats: Res<RunStats>,
+    director: Res<SpawnDirector>,
+    name: Res<PlayerName>,
+    mut high_scores: ResMut<HighScores>,
+){
+    // the player can only die once per run however many events arrive
+    if !death_events.iter().any(|death| players.contains(death.entity)){
+        return;
+    }
+    let rank = high_scores.record(HighScoreEntry{
+        name: name.0.clone(),
+        score: stats.score,
+        wave: director.wave,
+        duration: stats.time_survived,
+        date: unix_now(),
+    });
+    if rank.is_some(){
+        if let Err(error) = high_scores.save(){
+            warn!("could not save high scores: {}", error);
+        }
+    }
+}
+
+fn clear_last_rank(
+    mut high_scores: ResMut<HighScores>,
+){
+    high_scores.last_rank = None;
+}
+
+
+pub struct HighScorePlugin;
+
+impl Plugin for HighScorePlugin{
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HighScores>()
+        .init_resource::<PlayerName>()
+        .add_system(clear_last_rank.in_schedule(OnEnter(AppState::InGame)).run_if(starting_run))
+        // reads the player's death the frame it happens, before the game over
+        // transition stops this from running
+        .add_system(record_high_score.in_set(OnUpdate(AppState::InGame)).after(apply_damage));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, score: u32) -> HighScoreEntry {
+        HighScoreEntry { name: name.to_string(), score, wave: 1, duration: 60.0, date: 0 }
+    }
+
+    /// A fresh directory per test, since tests run in parallel.
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("honors_high_scores_{}_{}", std::process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn scores(table: &HighScoreTable) -> Vec<u32> {
+        table.entries.iter().map(|entry| entry.score).collect()
+    }
+
+    #[test]
+    fn insert_keeps_the_top_runs_in_order() {
+        let mut table = HighScoreTable::default();
+        for score in [30, 10, 50, 20, 40]{
+            table.insert(entry("a", score), 3);
+        }
+        assert_eq!(scores(&table), vec![50, 40, 30]);
+    }
+
+    #[test]
+    fn insert_returns_the_rank() {
+        let mut table = HighScoreTable::default();
+        assert_eq!(table.insert(entry("a", 20), 3), Some(0));
+        assert_eq!(table.insert(entry("b", 30), 3), Some(0));
+        assert_eq!(table.insert(entry("c", 10), 3), Some(2));
+        // ties go below the earlier run
+        assert_eq!(table.insert(entry("d", 20), 3), Some(2));
+        assert_eq!(table.insert(entry("e", 5), 3), None);
+        assert_eq!(scores(&table), vec![30, 20, 20]);
+        assert_eq!(table.entries[1].name, "a");
+    }
+
+    #[test]
+    fn names_are_sanitized() {
+        assert_eq!(sanitize_name("  ada  "), "ada");
+        assert_eq!(sanitize_name("a\nb\tc"), "abc");
+        assert_eq!(sanitize_name("a very long player name"), "a very long");
+        assert_eq!(sanitize_name(" \n "), "Player");
+
+        let mut table = HighScoreTable::default();
+        table.insert(entry("\u{7}bell", 10), 3);
+        assert_eq!(table.entries[0].name, "bell");
+    }
+
+    #[test]
+    fn date_string_is_a_utc_date() {
+        assert_eq!(entry("a", 0).date_string(), "1970-01-01");
+        let leap_day = HighScoreEntry { date: 951_782_400, ..entry("a", 0) };
+        assert_eq!(leap_day.date_string(), "2000-02-29");
+    }
+
+    #[test]
+    fn saved_table_loads_back() {
+        let path = temp_dir("round_trip").join("nested").join("highscores.ron");
+        let mut high_scores = HighScores::with_path(&path, 3);
+        assert!(high_scores.table.entries.is_empty());
+        high_scores.record(entry("a", 10));
+        high_scores.record(entry("b", 20));
+        high_scores.save().unwrap();
+
+        let loaded = HighScores::with_path(&path, 3);
+        assert_eq!(loaded.table, high_scores.table);
+        assert_eq!(loaded.last_rank, None);
+        assert!(!path.with_extension("ron.tmp").exists());
+    }
+
+    #[test]
+    fn loading_sorts_and_truncates() {
+        let path = temp_dir("sanitize").join("highscores.ron");
+        let table = HighScoreTable {
+            entries: vec![entry("a", 10), entry("b", 30), HighScoreEntry { duration: -1.0, ..entry("c", 99) }, entry("d", 20)],
+        };
+        fs::write(&path, ron::to_string(&table).unwrap()).unwrap();
+
+        let loaded = HighScores::with_path(&path, 2);
+        assert_eq!(scores(&loaded.table), vec![30, 20]);
+    }
+
+    #[test]
+    fn corrupt_file_is_moved_aside() {
+        let path = temp_dir("corrupt").join("highscores.ron");
+        fs::write(&path, "this is not ron").unwrap();
+
+        let high_scores = HighScores::with_path(&path, 3);
+        assert!(high_scores.table.entries.is_empty());
+        assert!(!path.exists());
+        assert_eq!(fs::read_to_string(path.with_extension("ron.corrupt")).unwrap(), "this is not ron");
+
+        high_scores.save().unwrap();
+        assert_eq!(HighScores::with_path(&path, 3).table, HighScoreTable::default());
+    }
+}
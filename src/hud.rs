@@ -1,24 +1,141 @@
-use crate::app_state::AppState;
+use crate::app_state::{starting_run, AppState, StateScoped};
+use crate::boss::Boss;
+use crate::buff::Buffs;
+use crate::director::{DirectorPhase, SpawnDirector};
+use crate::health::Health;
+use crate::manifest::FontHandles;
+use crate::player::Player;
+use crate::stats::{ComboSettings, RunStats};
+use crate::weapon::Arsenal;
 use bevy::{prelude::*};
 
 #[derive(Component)]
 pub struct HealthBar;
 
-fn setup(mut commands: Commands) {
+#[derive(Component)]
+pub struct WaveText;
+
+/// Score and the running combo.
+#[derive(Component)]
+pub struct ScoreText;
+
+/// Weapon in hand, its ammo and any active power ups.
+#[derive(Component)]
+pub struct WeaponText;
+
+/// Frame of the boss health bar, only shown while a boss is alive.
+#[derive(Component)]
+pub struct BossBar;
+
+#[derive(Component)]
+pub struct BossHealthBar;
+
+fn setup(mut commands: Commands, fonts: Res<FontHandles>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle{
+                font: fonts.get("main"),
+                font_size: 32.0,
+                color: Color::WHITE,
+            },
+        ).with_style(Style{
+            position_type: PositionType::Absolute,
+            position: UiRect { right: Val::Percent(1.), top: Val::Percent(1.), ..default()},
+            ..default()
+        }),
+        WaveText,
+        StateScoped(AppState::InGame),
+    ));
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle{
+                font: fonts.get("main"),
+                font_size: 24.0,
+                color: Color::WHITE,
+            },
+        ).with_style(Style{
+            position_type: PositionType::Absolute,
+            position: UiRect { right: Val::Percent(1.), bottom: Val::Percent(1.), ..default()},
+            ..default()
+        }),
+        WeaponText,
+        StateScoped(AppState::InGame),
+    ));
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle{
+                font: fonts.get("main"),
+                font_size: 32.0,
+                color: Color::WHITE,
+            },
+        ).with_style(Style{
+            position_type: PositionType::Absolute,
+            position: UiRect { left: Val::Percent(1.), top: Val::Percent(12.), ..default()},
+            ..default()
+        }),
+        ScoreText,
+        StateScoped(AppState::InGame),
+    ));
+
     commands
-        .spawn(NodeBundle {
-            style: Style {
-                size: Size {
-                    width: Val::Percent(50.0),
-                    height: Val::Percent(10.0),
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect { left: Val::Percent(20.), bottom: Val::Percent(3.), ..default()},
+                    size: Size {
+                        width: Val::Percent(60.0),
+                        height: Val::Percent(4.0),
+                    },
+                    justify_content: JustifyContent::FlexStart,
+                    ..default()
                 },
-                margin: UiRect { left: Val::Percent(1.), top: Val::Percent(1.), ..default()},
-                justify_content: JustifyContent::FlexStart,
+                background_color: BackgroundColor(Color::DARK_GRAY),
+                visibility: Visibility::Hidden,
                 ..default()
             },
-            background_color: BackgroundColor(Color::GRAY),
-            ..default()
-        })
+            BossBar,
+            StateScoped(AppState::InGame),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        size: Size {
+                            width: Val::Percent(100.0),
+                            height: Val::Percent(100.0),
+                        },
+                        ..default()
+                    },
+                    background_color: BackgroundColor(Color::PURPLE),
+                    ..default()
+                },
+                BossHealthBar,
+            ));
+        });
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size {
+                        width: Val::Percent(50.0),
+                        height: Val::Percent(10.0),
+                    },
+                    margin: UiRect { left: Val::Percent(1.), top: Val::Percent(1.), ..default()},
+                    justify_content: JustifyContent::FlexStart,
+                    ..default()
+                },
+                background_color: BackgroundColor(Color::GRAY),
+                ..default()
+            },
+            StateScoped(AppState::InGame),
+        ))
         .with_children(|parent| {
             parent.spawn((
                 NodeBundle {
@@ -38,10 +155,91 @@ fn setup(mut commands: Commands) {
         });
 }
 
+fn update_health_bar(
+    players: Query<&Health, (With<Player>, Changed<Health>)>,
+    mut health_bar: Query<&mut Style, With<HealthBar>>,
+){
+    let (Ok(health), Ok(mut style)) = (players.get_single(), health_bar.get_single_mut()) else{
+        return;
+    };
+    style.size.width = Val::Percent(health.fraction() * 100.0);
+}
+
+fn update_boss_bar(
+    bosses: Query<&Health, With<Boss>>,
+    mut boss_bar: Query<&mut Visibility, With<BossBar>>,
+    mut boss_health_bar: Query<&mut Style, With<BossHealthBar>>,
+){
+    let (Ok(mut visibility), Ok(mut style)) = (boss_bar.get_single_mut(), boss_health_bar.get_single_mut()) else{
+        return;
+    };
+    // several bosses at once share the bar
+    let (current, max) = bosses.iter().fold((0, 0), |(current, max), health| (current + health.current.max(0), max + health.max));
+    if max == 0{
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    *visibility = Visibility::Inherited;
+    style.size.width = Val::Percent(current as f32 / max as f32 * 100.0);
+}
+
+fn update_weapon_text(
+    players: Query<(&Arsenal, &Buffs), With<Player>>,
+    mut weapon_text: Query<&mut Text, With<WeaponText>>,
+){
+    let (Ok((arsenal, buffs)), Ok(mut text)) = (players.get_single(), weapon_text.get_single_mut()) else{
+        return;
+    };
+    let weapon = arsenal.current();
+    let mut value = match (weapon.ammo, weapon.max_ammo) {
+        (Some(ammo), Some(max_ammo)) => format!("{} {}/{}", weapon.name, ammo, max_ammo),
+        _ => weapon.name.clone(),
+    };
+    for buff in buffs.active.iter(){
+        let remaining = (buff.timer.duration() - buff.timer.elapsed()).as_secs_f32();
+        value += &format!("\n{:?} x{} {:.0}s", buff.power_up, buff.stacks, remaining.ceil());
+    }
+    text.sections[0].value = value;
+}
+
+fn update_score_text(
+    stats: Res<RunStats>,
+    combo_settings: Res<ComboSettings>,
+    mut score_text: Query<&mut Text, With<ScoreText>>,
+){
+    let Ok(mut text) = score_text.get_single_mut() else{
+        return;
+    };
+    text.sections[0].value = if stats.combo > 1 {
+        format!("Score {}  combo {} x{:.1}", stats.score, stats.combo, stats.multiplier(&combo_settings))
+    } else {
+        format!("Score {}", stats.score)
+    };
+}
+
+fn update_wave_text(
+    director: Res<SpawnDirector>,
+    mut wave_text: Query<&mut Text, With<WaveText>>,
+){
+    let Ok(mut text) = wave_text.get_single_mut() else{
+        return;
+    };
+    text.sections[0].value = match director.phase {
+        DirectorPhase::Intermission if director.wave == 0 => "Get ready".to_string(),
+        DirectorPhase::Intermission => format!("Wave {} cleared", director.wave),
+        _ => format!("Wave {} - {} left", director.wave, director.remaining),
+    };
+}
+
 pub struct HudPlugin;
 
 impl Plugin for HudPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(setup.in_schedule(OnEnter(AppState::InGame)));
+        app.add_system(setup.in_schedule(OnEnter(AppState::InGame)).run_if(starting_run))
+            .add_system(update_health_bar.in_set(OnUpdate(AppState::InGame)))
+            .add_system(update_wave_text.in_set(OnUpdate(AppState::InGame)))
+            .add_system(update_boss_bar.in_set(OnUpdate(AppState::InGame)))
+            .add_system(update_weapon_text.in_set(OnUpdate(AppState::InGame)))
+            .add_system(update_score_text.in_set(OnUpdate(AppState::InGame)));
     }
 }
@@ -0,0 +1,219 @@
+use bevy::asset::{HandleId, LoadState};
+use bevy::prelude::*;
+
+use crate::app_state::{AppState, StateScoped};
+use crate::audio::AudioController;
+use crate::enemy::EnemyMeshScenes;
+use crate::manifest::{apply_manifest, FontHandles, ManifestHandle};
+use crate::player::PlayerMeshScene;
+
+#[derive(Component)]
+pub struct LoadingBar;
+
+#[derive(Component)]
+pub struct LoadingText;
+
+/// How far the startup assets have come along.
+#[derive(Resource, Default, Debug)]
+pub struct LoadingProgress {
+    pub loaded: usize,
+    pub total: usize,
+    /// What went wrong so far, in the order it was found. Any error keeps
+    /// the game on the loading screen so it stays readable.
+    pub errors: Vec<String>,
+}
+
+impl LoadingProgress {
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.loaded as f32 / self.total as f32
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.errors.is_empty() && self.total > 0 && self.loaded == self.total
+    }
+
+    /// Logs `error` and shows it on the loading screen, once. Takes the
+    /// `ResMut` so repeats leave the resource unchanged and the screen idle.
+    pub fn report(progress: &mut ResMut<Self>, error: impl ToString) {
+        let error = error.to_string();
+        if !progress.errors.contains(&error){
+            error!("{}", error);
+            progress.errors.push(error);
+        }
+    }
+}
+
+/// Every handle the manifest handed out, which the game needs before it can
+/// leave the loading screen.
+fn tracked_handles(
+    audio_controller: &AudioController,
+    player_mesh: Option<&PlayerMeshScene>,
+    enemy_scenes: &EnemyMeshScenes,
+    fonts: &FontHandles,
+) -> Vec<HandleId> {
+    let mut handles: Vec<HandleId> = audio_controller.handles.iter()
+        .map(|named| named.handle.id())
+        .collect();
+    handles.extend(player_mesh.map(|mesh| mesh.0.id()));
+    handles.extend(enemy_scenes.0.values().map(|scene| scene.id()));
+    handles.extend(fonts.0.values().map(|font| font.id()));
+    handles
+}
+
+/// Builds the loading screen. It shows before the manifest is read, so it
+/// loads its font itself instead of waiting on `FontHandles`.
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+){
+    let font = asset_server.load("fonts/NotoSans-Black.ttf");
+    commands.spawn((Camera2dBundle::default(), StateScoped(AppState::Loading)));
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    gap: Size::all(Val::Px(16.0)),
+                    ..default()
+                },
+                background_color: BackgroundColor(Color::BLACK),
+                ..default()
+            },
+            StateScoped(AppState::Loading),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "Loading...",
+                    TextStyle{
+                        font: font.clone(),
+                        font_size: 40.0,
+                        color: Color::WHITE,
+                    },
+                ).with_text_alignment(TextAlignment::Center),
+                LoadingText,
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(50.0), Val::Px(24.0)),
+                        justify_content: JustifyContent::FlexStart,
+                        ..default()
+                    },
+                    background_color: BackgroundColor(Color::GRAY),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+                                ..default()
+                            },
+                            background_color: BackgroundColor(Color::GREEN),
+                            ..default()
+                        },
+                        LoadingBar,
+                    ));
+                });
+        });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn track_loading(
+    asset_server: Res<AssetServer>,
+    manifest: Option<Res<ManifestHandle>>,
+    audio_controller: Res<AudioController>,
+    player_mesh: Option<Res<PlayerMeshScene>>,
+    enemy_scenes: Res<EnemyMeshScenes>,
+    fonts: Res<FontHandles>,
+    mut progress: ResMut<LoadingProgress>,
+    mut next_state: ResMut<NextState<AppState>>,
+){
+    let Some(manifest) = manifest else{
+        return;
+    };
+    if asset_server.get_load_state(&manifest.handle) == LoadState::Failed{
+        LoadingProgress::report(
+            &mut progress,
+            format!("could not load the asset manifest {}", crate::manifest::MANIFEST_PATH),
+        );
+        return;
+    }
+    // nothing else to wait on until the manifest says what to load
+    if !manifest.applied{
+        return;
+    }
+    let handles = tracked_handles(&audio_controller, player_mesh.as_deref(), &enemy_scenes, &fonts);
+    if handles.is_empty(){
+        // nothing would ever finish, so say why instead of waiting forever
+        LoadingProgress::report(&mut progress, "the asset manifest lists nothing to load");
+        return;
+    }
+    let mut loaded = 0;
+    for handle in handles.iter().copied(){
+        match asset_server.get_load_state(handle) {
+            LoadState::Loaded => loaded += 1,
+            LoadState::Failed => {
+                let path = asset_server.get_handle_path(handle)
+                    .map_or_else(|| format!("{:?}", handle), |path| path.path().display().to_string());
+                LoadingProgress::report(&mut progress, format!("could not load {}", path));
+            }
+            _ => {}
+        }
+    }
+    if progress.total != handles.len() || progress.loaded != loaded{
+        progress.total = handles.len();
+        progress.loaded = loaded;
+    }
+    if progress.is_done(){
+        next_state.set(AppState::MainMenu);
+    }
+}
+
+fn update_loading_screen(
+    progress: Res<LoadingProgress>,
+    mut loading_bar: Query<&mut Style, With<LoadingBar>>,
+    mut loading_text: Query<&mut Text, With<LoadingText>>,
+){
+    if !progress.is_changed(){
+        return;
+    }
+    if let Ok(mut style) = loading_bar.get_single_mut(){
+        style.size.width = Val::Percent(progress.fraction() * 100.0);
+    }
+    let Ok(mut text) = loading_text.get_single_mut() else{
+        return;
+    };
+    let mut value = format!("Loading... {}/{}", progress.loaded, progress.total);
+    for error in progress.errors.iter(){
+        value += &format!("\n{}", error);
+    }
+    text.sections[0].value = value;
+    if !progress.errors.is_empty(){
+        text.sections[0].style.color = Color::RED;
+    }
+}
+
+
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin{
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LoadingProgress>()
+        .add_system(setup.in_schedule(OnEnter(AppState::Loading)))
+        // the manifest inserts the player's scene with commands, flush them so
+        // it is tracked the same frame
+        .add_systems(
+            (apply_manifest, apply_system_buffers, track_loading, update_loading_screen)
+                .chain()
+                .in_set(OnUpdate(AppState::Loading))
+        );
+    }
+}
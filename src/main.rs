@@ -1,5 +1,5 @@
-use app_state::AppState;
-use audio::{AddHandle, GetHandle};
+use app_state::{AppState, StateScoped};
+use audio::GetHandle;
 use bevy::{prelude::*, window::PrimaryWindow, utils::Duration};
 pub mod collision;
 pub mod audio;
@@ -9,9 +9,25 @@ pub mod player;
 pub mod app_state;
 pub mod menu;
 pub mod hud;
+pub mod health;
+pub mod weapon;
+pub mod director;
+pub mod steering;
+pub mod navigation;
+pub mod boss;
+pub mod pickup;
+pub mod buff;
+pub mod stats;
+pub mod highscore;
+pub mod game_over;
+pub mod pause;
+pub mod overlay;
+pub mod rewards;
+pub mod loading;
+pub mod manifest;
 
 use bevy_rapier3d::{prelude::*};
-use player::{DamageCooldown, PlayerInfo, PlayerMeshScene};
+use player::{PlayerInfo, PlayerMeshScene};
 use std::{env,};
 
 /* #region Test */
@@ -44,16 +60,61 @@ fn main() {
 
         })
         .add_plugin(SetupPlugin)
+        .add_plugin(manifest::ManifestPlugin)
+        .add_plugin(loading::LoadingPlugin)
         .add_plugin(menu::MenuPlugin)
         .add_plugin(audio::AudioPlugin)
         .add_plugin(bullet::BulletPlugin)
         .add_plugin(collision::CollisionPlugin)
+        .add_plugin(health::HealthPlugin)
         .add_plugin(enemy::EnemyPlugin)
+        .add_plugin(director::DirectorPlugin)
+        .add_plugin(steering::SteeringPlugin)
+        .add_plugin(navigation::NavigationPlugin)
+        .add_plugin(boss::BossPlugin)
+        .add_plugin(pickup::PickupPlugin)
+        .add_plugin(buff::BuffPlugin)
+        .add_plugin(stats::StatsPlugin)
+        .add_plugin(highscore::HighScorePlugin)
+        .add_plugin(game_over::GameOverPlugin)
+        .add_plugin(pause::PausePlugin)
+        .add_plugin(rewards::RewardsPlugin)
         .add_plugin(player::PlayerPlugin)
+        .add_plugin(weapon::WeaponPlugin)
         .add_plugin(hud::HudPlugin)
         .run();
 }
 
+/// Anything with a dynamic body that leaves this box is despawned.
+#[derive(Resource, Clone, Copy)]
+pub struct ArenaBounds{
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl ArenaBounds {
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+}
+
+fn reap_out_of_bounds(
+    bodies: Query<(Entity, &GlobalTransform, &RigidBody), Without<player::Player>>,
+    pooled: Query<(), With<bullet::Pooled>>,
+    bounds: Res<ArenaBounds>,
+    mut commands: Commands,
+){
+    for (entity, transform, body) in bodies.iter(){
+        // pooled bullets are expired by the pool itself
+        if pooled.contains(entity){
+            continue;
+        }
+        if *body == RigidBody::Dynamic && !bounds.contains(transform.translation()){
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
 //setup scene
 fn setup(
     mut commands: Commands,
@@ -66,9 +127,11 @@ fn setup(
             material: materials.add(Color::rgb(1.0, 0.0, 0.0).into()),
             ..default()
         })
+        .insert(StateScoped(AppState::InGame))
+        .insert(collision::Collidable{kind: collision::CollidableKind::Ground})
         .insert(Collider::cuboid(25.0, 0.1, 25.0));
     commands
-        .spawn(RigidBody::Fixed)
+        .spawn((RigidBody::Fixed, StateScoped(AppState::InGame)))
         .insert(PbrBundle {
             mesh: meshes.add(Mesh::from(shape::Box::new(1.0, 10.0, 50.0))),
             transform: Transform::from_xyz(25.0, 0.0, 0.0),
@@ -84,7 +147,7 @@ fn setup(
         .insert(Collider::cuboid(0.5, 10.0, 25.0));
 
     commands
-        .spawn(RigidBody::Fixed)
+        .spawn((RigidBody::Fixed, StateScoped(AppState::InGame)))
         .insert(PbrBundle {
             mesh: meshes.add(Mesh::from(shape::Box::new(1.0, 10.0, 50.0))),
             transform: Transform::from_xyz(-25.0, 0.0, 0.0),
@@ -100,7 +163,7 @@ fn setup(
         .insert(Collider::cuboid(0.5, 10.0, 25.0));
 
     commands
-        .spawn(RigidBody::Fixed)
+        .spawn((RigidBody::Fixed, StateScoped(AppState::InGame)))
         .insert(PbrBundle {
             mesh: meshes.add(Mesh::from(shape::Box::new(51.0, 10.0, 1.0))),
             transform: Transform::from_xyz(0.0, 0.0, 25.0),
@@ -116,7 +179,7 @@ fn setup(
         .insert(Collider::cuboid(25.0, 10.0, 0.5));
 
     commands
-        .spawn(RigidBody::Fixed)
+        .spawn((RigidBody::Fixed, StateScoped(AppState::InGame)))
         .insert(PbrBundle {
             mesh: meshes.add(Mesh::from(shape::Box::new(51.0, 10.0, 1.0))),
             transform: Transform::from_xyz(0.0, 0.0, -25.0),
@@ -131,34 +194,62 @@ fn setup(
         })
         .insert(Collider::cuboid(25.0, 10.0, 0.5));
 
-    commands.spawn(PointLightBundle {
-        point_light: PointLight {
-            intensity: 1500.0,
-            shadows_enabled: true,
+    // interior cover, kept clear of the player's start in the middle
+    let obstacle_material = materials.add(Color::rgb(0.2, 0.6, 0.2).into());
+    for (position, half_extents) in [
+        (Vec3::new(10.0, 1.5, 10.0), Vec3::new(1.5, 1.5, 1.5)),
+        (Vec3::new(-10.0, 1.5, 10.0), Vec3::new(1.5, 1.5, 1.5)),
+        (Vec3::new(10.0, 1.5, -10.0), Vec3::new(1.5, 1.5, 1.5)),
+        (Vec3::new(-10.0, 1.5, -10.0), Vec3::new(1.5, 1.5, 1.5)),
+        (Vec3::new(0.0, 1.5, 16.0), Vec3::new(6.0, 1.5, 0.5)),
+        (Vec3::new(0.0, 1.5, -16.0), Vec3::new(6.0, 1.5, 0.5)),
+        (Vec3::new(16.0, 1.5, 0.0), Vec3::new(0.5, 1.5, 6.0)),
+        (Vec3::new(-16.0, 1.5, 0.0), Vec3::new(0.5, 1.5, 6.0)),
+    ]{
+        commands
+            .spawn((RigidBody::Fixed, StateScoped(AppState::InGame)))
+            .insert(PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::Box::new(half_extents.x * 2.0, half_extents.y * 2.0, half_extents.z * 2.0))),
+                transform: Transform::from_translation(position),
+                material: obstacle_material.clone(),
+                ..default()
+            })
+            .insert(collision::Collidable{kind: collision::CollidableKind::Wall})
+            .insert(ActiveEvents::COLLISION_EVENTS)
+            .insert(Friction{
+                coefficient:0.0,
+                combine_rule: CoefficientCombineRule::Min
+            })
+            .insert(Collider::cuboid(half_extents.x, half_extents.y, half_extents.z));
+    }
+
+    commands.spawn((
+        PointLightBundle {
+            point_light: PointLight {
+                intensity: 1500.0,
+                shadows_enabled: true,
+                ..default()
+            },
+            transform: Transform::from_xyz(4.0, 8.0, 4.0),
             ..default()
         },
-        transform: Transform::from_xyz(4.0, 8.0, 4.0),
-        ..default()
-    });
+        StateScoped(AppState::InGame),
+    ));
 }
 
-fn load_assets(mut commands: Commands, server: Res<AssetServer>, mut audio_controller: ResMut<audio::AudioController>){
-    let player_mesh = server.load("robot.glb#Scene0");
-    let enemy_mesh = server.load("eyeball.glb#Scene0");
-    audio_controller.add_handle("bonk", server.load("sounds/bonk-gavin6049.ogg"));
-    audio_controller.add_handle("explosion", server.load("sounds/explosion-prof-mudkip.ogg"));
-    audio_controller.add_handle("gunshot", server.load("sounds/gunshot-jofae.ogg"));
-    audio_controller.add_handle("inferno", server.load("sounds/inferno-hvrl.ogg"));
-    audio_controller.add_handle("laser", server.load("sounds/laser-daleonfire.ogg"));
-    audio_controller.add_handle("music", server.load("sounds/music.ogg"));
-    audio_controller.add_handle("slam", server.load("sounds/slam-jofae.ogg"));
-    commands.insert_resource(player::PlayerMeshScene(player_mesh));
-    commands.insert_resource(enemy::EnemyMeshScene(enemy_mesh));
-    //create_player(player_mesh, commands);
+/// Asks for the asset manifest. Everything it lists is loaded once it
+/// arrives, while the loading screen is up.
+fn load_assets(
+    mut commands: Commands,
+    server: Res<AssetServer>,
+){
+    commands.insert_resource(manifest::ManifestHandle{
+        handle: server.load(manifest::MANIFEST_PATH),
+        applied: false,
+    });
 }
 
 fn create_player(player_mesh: Res<PlayerMeshScene>, mut commands: Commands) {
-    let mut timer = Timer::new(Duration::from_secs(1), TimerMode::Repeating);
     commands.spawn((
         RigidBody::Dynamic,
         LockedAxes::ROTATION_LOCKED,
@@ -171,10 +262,16 @@ fn create_player(player_mesh: Res<PlayerMeshScene>, mut commands: Commands) {
         ActiveEvents::COLLISION_EVENTS,
         ActiveCollisionTypes::all(),
         GravityScale(0.0),
-        player::DamageCooldown{timer: timer},
+        health::DamageCooldown::new(Duration::from_secs(1)),
+        health::Health::new(10),
         collision::Collidable{kind: collision::CollidableKind::Player},
-        player::Player{speed: 5.0, health: 10}
-    )).with_children(|children| {
+        player::Player{speed: 5.0},
+        weapon::Arsenal::default(),
+        weapon::WeaponState::default(),
+        buff::Buffs::default(),
+    ))
+    .insert(StateScoped(AppState::InGame))
+    .with_children(|children| {
         children.spawn(SceneBundle {
             scene: player_mesh.0.clone(),
             transform: Transform::default()
@@ -206,15 +303,6 @@ fn setup_state(
 }
 
 
-fn cleanup_scene(
-    mut commands: Commands,
-    mut entities: Query<Entity, Without<Window>>,
-){
-    for mut entity in entities.iter_mut(){
-        commands.entity(entity).despawn();
-    }
-}
-
 impl Plugin for SetupPlugin {
     fn build(&self, app: &mut App) {
         app.add_state::<app_state::AppState>();
@@ -224,12 +312,18 @@ impl Plugin for SetupPlugin {
                 forward: Vec3::NEG_Z,
                 rotation: Quat::IDENTITY,
             })
+            .insert_resource(ArenaBounds{
+                min: Vec3::new(-26.0, -5.0, -26.0),
+                max: Vec3::new(26.0, 20.0, 26.0),
+            })
             .add_system(setup_state)
+            .add_system(reap_out_of_bounds.in_set(OnUpdate(app_state::AppState::InGame)))
             .add_system(load_assets.on_startup())
-            .add_system(cleanup_scene.in_schedule(OnExit(app_state::AppState::InGame)))
-            .add_system(cleanup_scene.in_schedule(OnExit(app_state::AppState::MainMenu)))
-            .add_system(create_player.in_schedule(OnEnter(app_state::AppState::InGame)))
-            .add_system(setup.in_schedule(OnEnter(app_state::AppState::InGame)))
+            .add_system(create_player.in_schedule(OnEnter(app_state::AppState::InGame)).run_if(app_state::starting_run))
+            .add_system(setup.in_schedule(OnEnter(app_state::AppState::InGame)).run_if(app_state::starting_run))
             .add_system(print_test.in_schedule(OnEnter(app_state::AppState::MainMenu)));
+        for state in app_state::AppState::variants(){
+            app.add_system(app_state::despawn_scoped(state).in_schedule(OnExit(state)));
+        }
     }
 }
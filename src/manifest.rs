@@ -0,0 +1,246 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+
+use bevy::asset::{Asset, AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use serde::Deserialize;
+
+use crate::audio::{AddHandle, AudioController, DuplicateHandle};
+use crate::enemy::{EnemyMeshScenes, EnemyRegistry};
+use crate::loading::LoadingProgress;
+use crate::player::PlayerMeshScene;
+
+/// The manifest's path inside the asset folder. The `.manifest.ron` extension
+/// is what picks `AssetManifestLoader` for it.
+pub const MANIFEST_PATH: &str = "game.manifest.ron";
+
+/// One asset and the name the game asks for it by.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct AssetEntry {
+    pub name: String,
+    /// Relative to the asset folder, with an optional `#Label` for sub-assets.
+    pub path: String,
+}
+
+/// Everything the game loads at startup, read through the `AssetServer` so it
+/// follows `AssetPlugin::asset_folder` and works on any asset IO.
+#[derive(Deserialize, TypeUuid, Clone, Debug, Default, PartialEq)]
+#[uuid = "5b0e6a8e-3f2c-4c8e-9d61-8f3c1a7e2b94"]
+pub struct AssetManifest {
+    #[serde(default)]
+    pub sounds: Vec<AssetEntry>,
+    #[serde(default)]
+    pub scenes: Vec<AssetEntry>,
+    #[serde(default)]
+    pub fonts: Vec<AssetEntry>,
+}
+
+impl AssetManifest {
+    pub fn parse(bytes: &[u8]) -> Result<Self, ron::error::SpannedError> {
+        ron::de::from_bytes(bytes)
+    }
+}
+
+/// Problems with the manifest's contents. Files that fail to load are
+/// reported by the loading screen with the rest of the assets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ManifestError {
+    DuplicateName { section: &'static str, name: String },
+    UnknownName { section: &'static str, name: String },
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestError::DuplicateName { section, name } => write!(f, "{} entry \"{}\" is listed more than once", section, name),
+            ManifestError::UnknownName { section, name } => write!(f, "no {} entry named \"{}\" in the asset manifest", section, name),
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+#[derive(Default)]
+pub struct AssetManifestLoader;
+
+impl AssetLoader for AssetManifestLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let manifest = AssetManifest::parse(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(manifest));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["manifest.ron"]
+    }
+}
+
+/// The manifest being loaded, and whether its entries have been handed out.
+#[derive(Resource)]
+pub struct ManifestHandle {
+    pub handle: Handle<AssetManifest>,
+    pub applied: bool,
+}
+
+/// The fonts listed in the manifest, by name.
+#[derive(Resource, Clone, Default)]
+pub struct FontHandles(pub HashMap<String, Handle<Font>>);
+
+impl FontHandles {
+    /// The named font, or the default handle, which draws nothing, if the
+    /// manifest has not been applied or does not list it.
+    pub fn get(&self, name: &str) -> Handle<Font> {
+        self.0.get(name).cloned().unwrap_or_default()
+    }
+}
+
+/// Loads every entry into `handles` under its name with `load`, reporting
+/// names that are already taken. The first entry with a name wins.
+pub fn register_assets<T: Asset>(
+    section: &'static str,
+    entries: &[AssetEntry],
+    handles: &mut HashMap<String, Handle<T>>,
+    errors: &mut Vec<ManifestError>,
+    mut load: impl FnMut(&str) -> Handle<T>,
+){
+    for entry in entries.iter(){
+        match handles.entry(entry.name.clone()) {
+            Entry::Occupied(_) => errors.push(ManifestError::DuplicateName{section, name: entry.name.clone()}),
+            Entry::Vacant(slot) => {
+                slot.insert(load(&entry.path));
+            }
+        }
+    }
+}
+
+/// Starts loading everything in the manifest once it has loaded itself.
+#[allow(clippy::too_many_arguments)]
+pub fn apply_manifest(
+    mut commands: Commands,
+    server: Res<AssetServer>,
+    manifests: Res<Assets<AssetManifest>>,
+    manifest_handle: Option<ResMut<ManifestHandle>>,
+    mut audio_controller: ResMut<AudioController>,
+    enemy_registry: Res<EnemyRegistry>,
+    mut enemy_scenes: ResMut<EnemyMeshScenes>,
+    mut fonts: ResMut<FontHandles>,
+    mut progress: ResMut<LoadingProgress>,
+){
+    let Some(mut manifest_handle) = manifest_handle else{
+        return;
+    };
+    if manifest_handle.applied{
+        return;
+    }
+    let Some(manifest) = manifests.get(&manifest_handle.handle) else{
+        return;
+    };
+    manifest_handle.applied = true;
+
+    let mut errors = Vec::new();
+    for entry in manifest.sounds.iter(){
+        if let Err(DuplicateHandle(name)) = audio_controller.add_handle(&entry.name, server.load(entry.path.as_str())){
+            errors.push(ManifestError::DuplicateName{section: "sounds", name});
+        }
+    }
+    register_assets("fonts", &manifest.fonts, &mut fonts.0, &mut errors, |path| server.load(path));
+    let mut scenes = HashMap::new();
+    register_assets("scenes", &manifest.scenes, &mut scenes, &mut errors, |path| server.load(path));
+
+    match scenes.get("player") {
+        Some(player_mesh) => commands.insert_resource(PlayerMeshScene(player_mesh.clone())),
+        None => errors.push(ManifestError::UnknownName{section: "scenes", name: "player".to_string()}),
+    }
+    for enemy_type in enemy_registry.types.iter(){
+        match scenes.get(&enemy_type.scene) {
+            Some(scene) => {
+                enemy_scenes.0.insert(enemy_type.kind, scene.clone());
+            }
+            None => errors.push(ManifestError::UnknownName{section: "scenes", name: enemy_type.scene.clone()}),
+        }
+    }
+    for error in errors{
+        LoadingProgress::report(&mut progress, error);
+    }
+}
+
+
+pub struct ManifestPlugin;
+
+impl Plugin for ManifestPlugin{
+    fn build(&self, app: &mut App) {
+        app.add_asset::<AssetManifest>()
+        .init_asset_loader::<AssetManifestLoader>()
+        .init_resource::<FontHandles>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::HandleId;
+
+    fn entry(name: &str, path: &str) -> AssetEntry {
+        AssetEntry { name: name.to_string(), path: path.to_string() }
+    }
+
+    #[test]
+    fn parses_every_section() {
+        let manifest = AssetManifest::parse(br#"(
+            // comments are fine
+            sounds: [(name: "bonk", path: "sounds/bonk.ogg")],
+            scenes: [(name: "player", path: "robot.glb#Scene0")],
+            fonts: [(name: "main", path: "fonts/main.ttf")],
+        )"#).unwrap();
+        assert_eq!(manifest.sounds, vec![entry("bonk", "sounds/bonk.ogg")]);
+        assert_eq!(manifest.scenes, vec![entry("player", "robot.glb#Scene0")]);
+        assert_eq!(manifest.fonts, vec![entry("main", "fonts/main.ttf")]);
+    }
+
+    #[test]
+    fn missing_sections_are_empty() {
+        let manifest = AssetManifest::parse(br#"(sounds: [(name: "bonk", path: "bonk.ogg")])"#).unwrap();
+        assert_eq!(manifest.sounds.len(), 1);
+        assert!(manifest.scenes.is_empty());
+        assert!(manifest.fonts.is_empty());
+    }
+
+    #[test]
+    fn invalid_manifest_is_an_error() {
+        assert!(AssetManifest::parse(b"(sounds: [(name: \"bonk\")])").is_err());
+        assert!(AssetManifest::parse(b"not a manifest").is_err());
+    }
+
+    #[test]
+    fn shipped_manifest_parses() {
+        let bytes = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/game.manifest.ron")).unwrap();
+        let manifest = AssetManifest::parse(&bytes).unwrap();
+        assert!(manifest.scenes.iter().any(|scene| scene.name == "player"));
+    }
+
+    #[test]
+    fn register_assets_reports_duplicate_names() {
+        let entries = [entry("a", "one.ttf"), entry("b", "two.ttf"), entry("a", "three.ttf")];
+        let mut loaded = Vec::new();
+        let mut handles: HashMap<String, Handle<Font>> = HashMap::new();
+        let mut errors = Vec::new();
+        register_assets("fonts", &entries, &mut handles, &mut errors, |path| {
+            loaded.push(path.to_string());
+            Handle::weak(HandleId::random::<Font>())
+        });
+
+        assert_eq!(errors, vec![ManifestError::DuplicateName{section: "fonts", name: "a".to_string()}]);
+        // the first entry keeps the name and the duplicate is never loaded
+        assert_eq!(loaded, vec!["one.ttf", "two.ttf"]);
+        assert_eq!(handles.len(), 2);
+    }
+}
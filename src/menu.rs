@@ -1,12 +1,15 @@
 use bevy::{prelude::*, reflect::erased_serde::__private::serde::__private::de};
-use crate::app_state::AppState;
+use crate::app_state::{AppState, StateScoped};
+use crate::highscore::HighScores;
+use crate::manifest::FontHandles;
 
 fn setup(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    fonts: Res<FontHandles>,
+    high_scores: Res<HighScores>,
 ){
-    commands.spawn(Camera2dBundle::default());
-    commands.spawn(
+    commands.spawn((Camera2dBundle::default(), StateScoped(AppState::MainMenu)));
+    commands.spawn((
         NodeBundle {
             style: Style {
                 size: Size::width(Val::Percent(100.0)),
@@ -16,11 +19,12 @@ fn setup(
             background_color: BackgroundColor(Color::PURPLE),
             ..default()
         },
-    ).with_children(|parent|{
+        StateScoped(AppState::MainMenu),
+    )).with_children(|parent|{
         parent.spawn(TextBundle::from_section(
             "Press Space to Start!",
             TextStyle{
-                font: asset_server.load("fonts/NotoSans-Black.ttf"),
+                font: fonts.get("main"),
                 font_size: 100.0,
                 color: Color::GREEN,
             },
@@ -31,6 +35,62 @@ fn setup(
 
         })
         .with_text_alignment(TextAlignment::Left));
+        parent.spawn(NodeBundle{
+            style: Style{
+                position_type: PositionType::Absolute,
+                position: UiRect { bottom: Val::Percent(5.), ..default()},
+                margin: UiRect { left: Val::Auto, right: Val::Auto, ..default()},
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        }).with_children(|parent|{
+            let style = TextStyle{
+                font: fonts.get("main"),
+                font_size: 28.0,
+                color: Color::WHITE,
+            };
+            parent.spawn(TextBundle::from_section("High Scores", style.clone()));
+            if high_scores.table.entries.is_empty(){
+                parent.spawn(TextBundle::from_section("No runs yet", style));
+                return;
+            }
+            let entries = &high_scores.table.entries;
+            // the font isn't monospaced, so each column gets its own node
+            let columns: [(AlignItems, Vec<String>); 6] = [
+                (AlignItems::FlexEnd, (1..=entries.len()).map(|rank| format!("{}.", rank)).collect()),
+                (AlignItems::FlexStart, entries.iter().map(|entry| entry.name.clone()).collect()),
+                (AlignItems::FlexEnd, entries.iter().map(|entry| entry.score.to_string()).collect()),
+                (AlignItems::FlexStart, entries.iter().map(|entry| format!("wave {}", entry.wave)).collect()),
+                (AlignItems::FlexEnd, entries.iter()
+                    .map(|entry| format!("{}:{:02}", entry.duration as u32 / 60, entry.duration as u32 % 60))
+                    .collect()),
+                (AlignItems::FlexStart, entries.iter().map(|entry| entry.date_string()).collect()),
+            ];
+            parent.spawn(NodeBundle{
+                style: Style{
+                    gap: Size::width(Val::Px(20.0)),
+                    ..default()
+                },
+                ..default()
+            }).with_children(|parent|{
+                for (align_items, cells) in columns{
+                    parent.spawn(NodeBundle{
+                        style: Style{
+                            flex_direction: FlexDirection::Column,
+                            align_items,
+                            ..default()
+                        },
+                        ..default()
+                    }).with_children(|parent|{
+                        for cell in cells{
+                            parent.spawn(TextBundle::from_section(cell, style.clone()));
+                        }
+                    });
+                }
+            });
+        });
     });    
     
 }
@@ -0,0 +1,468 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use bevy::utils::Duration;
+use bevy_rapier3d::prelude::*;
+use rand::Rng;
+
+use crate::app_state::AppState;
+use crate::collision::{Collidable, CollidableKind};
+use crate::player::PlayerInfo;
+use crate::steering::apply_steering;
+use crate::ArenaBounds;
+
+#[derive(Resource, Clone, Copy)]
+pub struct NavGridSettings {
+    pub cell_size: f32,
+    /// Obstacles are grown by this much so agents don't clip corners.
+    pub clearance: f32,
+}
+
+impl Default for NavGridSettings {
+    fn default() -> Self {
+        NavGridSettings { cell_size: 1.0, clearance: 0.5 }
+    }
+}
+
+/// Walkable cells of the arena floor on the XZ plane. Doesn't touch the ECS,
+/// so it can be built and queried on its own.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct NavGrid {
+    /// World position of the corner of cell (0, 0).
+    pub origin: Vec2,
+    pub cell_size: f32,
+    pub width: usize,
+    pub height: usize,
+    blocked: Vec<bool>,
+}
+
+type Cell = (usize, usize);
+
+#[derive(Clone, Copy, PartialEq)]
+struct OpenNode {
+    cost: f32,
+    index: usize,
+}
+
+impl Eq for OpenNode {}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed so the heap pops the cheapest node
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl NavGrid {
+    /// An empty grid covering `min` to `max`.
+    pub fn new(min: Vec2, max: Vec2, cell_size: f32) -> Self {
+        let size = ((max - min) / cell_size).ceil().max(Vec2::ZERO);
+        let width = size.x as usize;
+        let height = size.y as usize;
+        NavGrid { origin: min, cell_size, width, height, blocked: vec![false; width * height] }
+    }
+
+    /// Builds a grid with every cell touched by an obstacle rectangle, grown by
+    /// `clearance`, blocked.
+    pub fn bake(
+        min: Vec2,
+        max: Vec2,
+        cell_size: f32,
+        clearance: f32,
+        obstacles: impl IntoIterator<Item = (Vec2, Vec2)>,
+    ) -> Self {
+        let mut grid = NavGrid::new(min, max, cell_size);
+        for (obstacle_min, obstacle_max) in obstacles{
+            grid.block_area(obstacle_min - clearance, obstacle_max + clearance);
+        }
+        grid
+    }
+
+    pub fn block_area(&mut self, min: Vec2, max: Vec2) {
+        if self.width == 0 || self.height == 0{
+            return;
+        }
+        let from = ((min - self.origin) / self.cell_size).floor();
+        let to = ((max - self.origin) / self.cell_size).floor();
+        if to.x < 0.0 || to.y < 0.0 || from.x >= self.width as f32 || from.y >= self.height as f32{
+            return;
+        }
+        let from_x = from.x.max(0.0) as usize;
+        let from_y = from.y.max(0.0) as usize;
+        let to_x = (to.x as usize).min(self.width - 1);
+        let to_y = (to.y as usize).min(self.height - 1);
+        for y in from_y..=to_y{
+            for x in from_x..=to_x{
+                self.blocked[y * self.width + x] = true;
+            }
+        }
+    }
+
+    pub fn cell(&self, position: Vec3) -> Option<Cell> {
+        let local = (Vec2::new(position.x, position.z) - self.origin) / self.cell_size;
+        if local.x < 0.0 || local.y < 0.0{
+            return None;
+        }
+        let (x, y) = (local.x as usize, local.y as usize);
+        (x < self.width && y < self.height).then_some((x, y))
+    }
+
+    /// Middle of `cell` on the floor.
+    pub fn center(&self, (x, y): Cell) -> Vec3 {
+        let center = self.origin + (Vec2::new(x as f32, y as f32) + 0.5) * self.cell_size;
+        Vec3::new(center.x, 0.0, center.y)
+    }
+
+    pub fn is_walkable(&self, (x, y): Cell) -> bool {
+        x < self.width && y < self.height && !self.blocked[y * self.width + x]
+    }
+
+    /// The closest walkable cell to `cell`, searching outwards ring by ring.
+    pub fn nearest_walkable(&self, cell: Cell, max_radius: usize) -> Option<Cell> {
+        if self.is_walkable(cell){
+            return Some(cell);
+        }
+        let (cx, cy) = (cell.0 as isize, cell.1 as isize);
+        for radius in 1..=max_radius as isize{
+            for dy in -radius..=radius{
+                for dx in -radius..=radius{
+                    if dx.abs() != radius && dy.abs() != radius{
+                        continue;
+                    }
+                    let (x, y) = (cx + dx, cy + dy);
+                    if x >= 0 && y >= 0 && self.is_walkable((x as usize, y as usize)){
+                        return Some((x as usize, y as usize));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Whether a straight walk from `from` to `to` only crosses walkable cells.
+    pub fn line_of_sight(&self, from: Vec3, to: Vec3) -> bool {
+        let delta = Vec3::new(to.x - from.x, 0.0, to.z - from.z);
+        let steps = (delta.length() / (self.cell_size * 0.25)).ceil().max(1.0) as usize;
+        (0..=steps).all(|step| {
+            let point = from + delta * (step as f32 / steps as f32);
+            self.cell(point).is_some_and(|cell| self.is_walkable(cell))
+        })
+    }
+
+    fn neighbours(&self, (x, y): Cell) -> impl Iterator<Item = (Cell, f32)> + '_ {
+        const OFFSETS: [(isize, isize); 8] = [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)];
+        OFFSETS.iter().filter_map(move |&(dx, dy)| {
+            let (nx, ny) = (x as isize + dx, y as isize + dy);
+            if nx < 0 || ny < 0{
+                return None;
+            }
+            let next = (nx as usize, ny as usize);
+            if !self.is_walkable(next){
+                return None;
+            }
+            // no cutting diagonally past the corner of a blocked cell
+            if dx != 0 && dy != 0 && (!self.is_walkable((next.0, y)) || !self.is_walkable((x, next.1))){
+                return None;
+            }
+            let cost = if dx != 0 && dy != 0 { std::f32::consts::SQRT_2 } else { 1.0 };
+            Some((next, cost))
+        })
+    }
+
+    /// A* from `from` to `to`. The returned waypoints skip the start, end at
+    /// `to` (or the nearest walkable cell to it) and drop every corner that
+    /// can be seen past.
+    pub fn find_path(&self, from: Vec3, to: Vec3) -> Option<Vec<Vec3>> {
+        let start = self.nearest_walkable(self.cell(from)?, 3)?;
+        let goal_cell = self.cell(to)?;
+        let goal = self.nearest_walkable(goal_cell, 3)?;
+        let index = |(x, y): Cell| y * self.width + x;
+        let heuristic = |(x, y): Cell| {
+            let dx = (x as f32 - goal.0 as f32).abs();
+            let dy = (y as f32 - goal.1 as f32).abs();
+            dx.max(dy) + (std::f32::consts::SQRT_2 - 1.0) * dx.min(dy)
+        };
+
+        let mut cost_so_far = vec![f32::INFINITY; self.width * self.height];
+        let mut came_from = vec![usize::MAX; self.width * self.height];
+        let mut open = BinaryHeap::new();
+        cost_so_far[index(start)] = 0.0;
+        open.push(OpenNode { cost: heuristic(start), index: index(start) });
+
+        while let Some(OpenNode { cost, index: current }) = open.pop(){
+            let cell = (current % self.width, current / self.width);
+            if cell == goal{
+                break;
+            }
+            // stale entry for a node already reached more cheaply
+            if cost - heuristic(cell) > cost_so_far[current]{
+                continue;
+            }
+            for (next, step) in self.neighbours(cell){
+                let next_cost = cost_so_far[current] + step;
+                if next_cost < cost_so_far[index(next)]{
+                    cost_so_far[index(next)] = next_cost;
+                    came_from[index(next)] = current;
+                    open.push(OpenNode { cost: next_cost + heuristic(next), index: index(next) });
+                }
+            }
+        }
+
+        if cost_so_far[index(goal)].is_infinite(){
+            return None;
+        }
+        let mut cells = vec![goal];
+        let mut current = index(goal);
+        while current != index(start){
+            current = came_from[current];
+            cells.push((current % self.width, current / self.width));
+        }
+        cells.reverse();
+
+        let mut points: Vec<Vec3> = cells.into_iter().map(|cell| self.center(cell)).collect();
+        if goal == goal_cell{
+            *points.last_mut()? = Vec3::new(to.x, 0.0, to.z);
+        }
+        Some(self.smooth(Vec3::new(from.x, 0.0, from.z), points))
+    }
+
+    /// Drops waypoints that can be skipped by walking straight to a later one.
+    fn smooth(&self, from: Vec3, points: Vec<Vec3>) -> Vec<Vec3> {
+        let mut smoothed = Vec::new();
+        let mut anchor = from;
+        let mut index = 0;
+        while index < points.len(){
+            let mut furthest = index;
+            while furthest + 1 < points.len() && self.line_of_sight(anchor, points[furthest + 1]){
+                furthest += 1;
+            }
+            smoothed.push(points[furthest]);
+            anchor = points[furthest];
+            index = furthest + 1;
+        }
+        smoothed
+    }
+}
+
+/// Something that walks the nav grid towards the player. The steering seek
+/// behaviour heads for the first waypoint instead of the player while there
+/// is one.
+#[derive(Component, Clone, Debug)]
+pub struct NavAgent {
+    pub path: Vec<Vec3>,
+    pub repath: Timer,
+    /// Where the player was when the path was last found.
+    goal: Option<Vec3>,
+}
+
+impl NavAgent {
+    pub fn new(repath_interval: f32) -> Self {
+        let mut repath = Timer::from_seconds(repath_interval, TimerMode::Repeating);
+        // spread the agents' searches over the interval
+        repath.set_elapsed(Duration::from_secs_f32(rand::thread_rng().gen_range(0.0, repath_interval)));
+        NavAgent { path: Vec::new(), repath, goal: None }
+    }
+
+    pub fn next_waypoint(&self) -> Option<Vec3> {
+        self.path.first().copied()
+    }
+}
+
+impl Default for NavAgent {
+    fn default() -> Self {
+        NavAgent::new(0.5)
+    }
+}
+
+/// Rebuilds the grid whenever a fixed wall appears or moves.
+fn bake_nav_grid(
+    walls: Query<(Ref<GlobalTransform>, &Collider, &RigidBody, &Collidable)>,
+    mut grid: ResMut<NavGrid>,
+    settings: Res<NavGridSettings>,
+    bounds: Res<ArenaBounds>,
+){
+    let is_wall = |body: &RigidBody, collidable: &Collidable| *body == RigidBody::Fixed && collidable.kind == CollidableKind::Wall;
+    if !walls.iter().any(|(transform, _, body, collidable)| transform.is_changed() && is_wall(body, collidable)){
+        return;
+    }
+
+    let obstacles = walls.iter()
+        .filter(|(_, _, body, collidable)| is_wall(body, collidable))
+        .map(|(transform, collider, _, _)| {
+            let aabb = collider.raw.compute_local_aabb();
+            let (mins, maxs) = (aabb.mins, aabb.maxs);
+            let mut min = Vec2::splat(f32::INFINITY);
+            let mut max = Vec2::splat(f32::NEG_INFINITY);
+            for corner in 0..8{
+                let local = Vec3::new(
+                    if corner & 1 == 0 { mins.x } else { maxs.x },
+                    if corner & 2 == 0 { mins.y } else { maxs.y },
+                    if corner & 4 == 0 { mins.z } else { maxs.z },
+                );
+                let world = transform.transform_point(local);
+                min = min.min(Vec2::new(world.x, world.z));
+                max = max.max(Vec2::new(world.x, world.z));
+            }
+            (min, max)
+        });
+    *grid = NavGrid::bake(
+        Vec2::new(bounds.min.x, bounds.min.z),
+        Vec2::new(bounds.max.x, bounds.max.z),
+        settings.cell_size,
+        settings.clearance,
+        obstacles,
+    );
+}
+
+fn follow_paths(
+    mut agents: Query<(&Transform, &mut NavAgent)>,
+    grid: Res<NavGrid>,
+    player_info: Res<PlayerInfo>,
+    time: Res<Time>,
+){
+    for (transform, mut agent) in agents.iter_mut(){
+        let position = transform.translation;
+        agent.repath.tick(time.delta());
+        if agent.repath.just_finished(){
+            let player_moved = !agent.goal.is_some_and(|goal| goal.distance(player_info.position) <= grid.cell_size);
+            if player_moved || agent.path.is_empty(){
+                agent.goal = Some(player_info.position);
+                agent.path = if grid.line_of_sight(position, player_info.position) {
+                    Vec::new()
+                } else {
+                    grid.find_path(position, player_info.position).unwrap_or_default()
+                };
+            }
+        }
+
+        let flat_position = Vec3::new(position.x, 0.0, position.z);
+        while agent.next_waypoint().is_some_and(|waypoint| waypoint.distance(flat_position) < grid.cell_size * 0.5){
+            agent.path.remove(0);
+        }
+    }
+}
+
+
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin{
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NavGridSettings>()
+        .init_resource::<NavGrid>()
+        .add_systems((
+            // walls spawned this frame only have a world position once it's propagated
+            bake_nav_grid
+                .in_base_set(CoreSet::PostUpdate)
+                .after(TransformSystem::TransformPropagate)
+                .run_if(in_state(AppState::InGame)),
+            follow_paths.in_set(OnUpdate(AppState::InGame)).before(apply_steering),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid() -> NavGrid {
+        NavGrid::new(Vec2::ZERO, Vec2::splat(10.0), 1.0)
+    }
+
+    /// Walks every leg of `path` from `from` in small steps, checking that
+    /// each point lands on a walkable cell.
+    fn assert_walkable(grid: &NavGrid, from: Vec3, path: &[Vec3]) {
+        let mut anchor = from;
+        for waypoint in path{
+            for step in 0..=100{
+                let point = anchor.lerp(*waypoint, step as f32 / 100.0);
+                let cell = grid.cell(point).expect("path left the grid");
+                assert!(grid.is_walkable(cell), "path crosses blocked cell {:?} between {} and {}", cell, anchor, waypoint);
+            }
+            anchor = *waypoint;
+        }
+    }
+
+    #[test]
+    fn block_area_marks_covered_cells() {
+        let mut grid = grid();
+        grid.block_area(Vec2::new(4.0, 0.0), Vec2::new(4.5, 7.5));
+        for y in 0..=7{
+            assert!(!grid.is_walkable((4, y)));
+        }
+        assert!(grid.is_walkable((4, 8)));
+        assert!(grid.is_walkable((3, 0)));
+        assert!(grid.is_walkable((5, 0)));
+    }
+
+    #[test]
+    fn open_grid_walks_straight_to_the_goal() {
+        let grid = grid();
+        let from = Vec3::new(1.5, 0.0, 1.5);
+        let to = Vec3::new(8.5, 0.0, 8.5);
+        assert!(grid.line_of_sight(from, to));
+        assert_eq!(grid.find_path(from, to), Some(vec![to]));
+    }
+
+    #[test]
+    fn path_goes_around_a_wall() {
+        let mut grid = grid();
+        grid.block_area(Vec2::new(4.0, 0.0), Vec2::new(4.5, 7.5));
+        let from = Vec3::new(1.5, 0.0, 1.5);
+        let to = Vec3::new(8.5, 0.0, 1.5);
+        assert!(!grid.line_of_sight(from, to));
+
+        let path = grid.find_path(from, to).expect("the wall has a gap at the top");
+        assert_eq!(path.last(), Some(&to));
+        assert!(path.iter().any(|waypoint| waypoint.z > 8.0), "path should pass the end of the wall: {:?}", path);
+        assert_walkable(&grid, from, &path);
+    }
+
+    #[test]
+    fn enclosed_goal_has_no_path() {
+        let mut grid = grid();
+        // a ring of blocked cells around (7, 7)
+        grid.block_area(Vec2::new(5.0, 5.0), Vec2::new(9.5, 5.5));
+        grid.block_area(Vec2::new(5.0, 9.0), Vec2::new(9.5, 9.5));
+        grid.block_area(Vec2::new(5.0, 5.0), Vec2::new(5.5, 9.5));
+        grid.block_area(Vec2::new(9.0, 5.0), Vec2::new(9.5, 9.5));
+        assert_eq!(grid.find_path(Vec3::new(1.5, 0.0, 1.5), Vec3::new(7.5, 0.0, 7.5)), None);
+    }
+
+    #[test]
+    fn blocked_start_or_goal_uses_the_nearest_walkable_cell() {
+        let mut grid = grid();
+        grid.block_area(Vec2::new(1.0, 1.0), Vec2::new(1.5, 1.5));
+        grid.block_area(Vec2::new(8.0, 8.0), Vec2::new(8.5, 8.5));
+        let from = Vec3::new(1.5, 0.0, 1.5);
+        let to = Vec3::new(8.5, 0.0, 8.5);
+
+        let path = grid.find_path(from, to).expect("blocked ends should snap to open cells");
+        let last = *path.last().unwrap();
+        assert_ne!(last, to);
+        assert!(grid.is_walkable(grid.cell(last).unwrap()));
+        assert!(last.distance(to) < 2.0);
+        assert_walkable(&grid, path[0], &path[1..]);
+    }
+
+    #[test]
+    fn smoothing_never_cuts_through_blocked_cells() {
+        let mut grid = grid();
+        // a zig-zag the straight line would cut through twice
+        grid.block_area(Vec2::new(3.0, 0.0), Vec2::new(3.5, 7.5));
+        grid.block_area(Vec2::new(6.0, 2.0), Vec2::new(6.5, 9.5));
+        let from = Vec3::new(1.5, 0.0, 1.5);
+        let to = Vec3::new(8.5, 0.0, 1.5);
+
+        let path = grid.find_path(from, to).expect("the zig-zag is open");
+        assert!(path.len() >= 2, "corners can't all be skipped: {:?}", path);
+        assert_walkable(&grid, from, &path);
+    }
+}
@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::app_state::{AppState, StateScoped};
+
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVERED_COLOR: Color = Color::rgb(0.3, 0.3, 0.3);
+
+/// Stops the physics step while an overlay covers the frozen world.
+pub fn freeze_simulation(
+    mut rapier_config: ResMut<RapierConfiguration>,
+){
+    rapier_config.physics_pipeline_active = false;
+}
+
+pub fn resume_simulation(
+    mut rapier_config: ResMut<RapierConfiguration>,
+){
+    rapier_config.physics_pipeline_active = true;
+}
+
+/// Spawns a full-screen column over the HUD, dimmed by `dim`, that is
+/// despawned when `state` ends.
+pub fn spawn_overlay(
+    commands: &mut Commands,
+    state: AppState,
+    dim: f32,
+    children: impl FnOnce(&mut ChildBuilder),
+){
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    gap: Size::all(Val::Px(16.0)),
+                    ..default()
+                },
+                background_color: BackgroundColor(Color::rgba(0.0, 0.0, 0.0, dim)),
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            StateScoped(state),
+        ))
+        .with_children(children);
+}
+
+pub fn spawn_button<T: Component>(parent: &mut ChildBuilder, button: T, label: &str, font: &Handle<Font>){
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    size: Size::new(Val::Px(260.0), Val::Px(60.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: BackgroundColor(BUTTON_COLOR),
+                ..default()
+            },
+            button,
+        ))
+        .with_children(|button| {
+            button.spawn(TextBundle::from_section(
+                label,
+                TextStyle{
+                    font: font.clone(),
+                    font_size: 32.0,
+                    color: Color::WHITE,
+                },
+            ));
+        });
+}
+
+/// Highlights hovered buttons and returns the one clicked this frame, if any.
+pub fn clicked_button<T: Component + Copy>(
+    buttons: &mut Query<(&Interaction, &T, &mut BackgroundColor), Changed<Interaction>>,
+) -> Option<T> {
+    let mut choice = None;
+    for (interaction, button, mut color) in buttons.iter_mut(){
+        match interaction {
+            Interaction::Clicked => choice = Some(*button),
+            Interaction::Hovered => *color = BackgroundColor(BUTTON_HOVERED_COLOR),
+            Interaction::None => *color = BackgroundColor(BUTTON_COLOR),
+        }
+    }
+    choice
+}
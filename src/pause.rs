@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+
+use crate::app_state::*;
+use crate::audio::*;
+use crate::manifest::FontHandles;
+use crate::overlay::*;
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauseButton {
+    Resume,
+    MainMenu,
+}
+
+/// How loud the music stays while paused, relative to normal.
+const DUCKED_VOLUME: f32 = 0.3;
+
+fn pause_game(
+    keys: Res<Input<KeyCode>>,
+    mut next_state: ResMut<NextState<AppState>>,
+){
+    if keys.just_pressed(KeyCode::Escape){
+        next_state.set(AppState::Paused);
+    }
+}
+
+fn duck_music(
+    audio_controller: Res<AudioController>,
+    sinks: Res<Assets<AudioSink>>,
+){
+    audio_controller.set_music_volume(audio_controller.music_volume * DUCKED_VOLUME, &sinks);
+}
+
+fn restore_music(
+    audio_controller: Res<AudioController>,
+    sinks: Res<Assets<AudioSink>>,
+){
+    audio_controller.set_music_volume(audio_controller.music_volume, &sinks);
+}
+
+fn setup(
+    mut commands: Commands,
+    fonts: Res<FontHandles>,
+){
+    let font = fonts.get("main");
+    spawn_overlay(&mut commands, AppState::Paused, 0.5, |parent| {
+        parent.spawn(TextBundle::from_section(
+            "PAUSED",
+            TextStyle{
+                font: font.clone(),
+                font_size: 80.0,
+                color: Color::WHITE,
+            },
+        ));
+        spawn_button(parent, PauseButton::Resume, "Resume", &font);
+        spawn_button(parent, PauseButton::MainMenu, "Main Menu", &font);
+    });
+}
+
+fn pause_menu(
+    mut buttons: Query<(&Interaction, &PauseButton, &mut BackgroundColor), Changed<Interaction>>,
+    keys: Res<Input<KeyCode>>,
+    mut next_state: ResMut<NextState<AppState>>,
+){
+    let mut choice = clicked_button(&mut buttons);
+    if keys.just_pressed(KeyCode::Escape){
+        choice = Some(PauseButton::Resume);
+    }
+    match choice {
+        Some(PauseButton::Resume) => next_state.set(AppState::InGame),
+        Some(PauseButton::MainMenu) => next_state.set(AppState::MainMenu),
+        None => {}
+    }
+}
+
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin{
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Resuming>()
+        .add_systems((
+            freeze_simulation.in_schedule(OnEnter(AppState::Paused)),
+            duck_music.in_schedule(OnEnter(AppState::Paused)),
+            setup.in_schedule(OnEnter(AppState::Paused)),
+        ))
+        .add_systems((
+            resume_simulation.in_schedule(OnExit(AppState::Paused)),
+            restore_music.in_schedule(OnExit(AppState::Paused)),
+            track_resuming.in_schedule(OnExit(AppState::Paused)),
+            track_resuming.in_schedule(OnExit(AppState::InGame)),
+        ))
+        .add_system(pause_game.in_set(OnUpdate(AppState::InGame)))
+        .add_system(pause_menu.in_set(OnUpdate(AppState::Paused)));
+    }
+}
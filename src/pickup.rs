@@ -0,0 +1,300 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use rand::Rng;
+
+use crate::app_state::{AppState, StateScoped};
+use crate::audio::*;
+use crate::buff::{BuffRules, Buffs};
+use crate::collision::*;
+use crate::health::Health;
+use crate::player::PlayerInfo;
+use crate::stats::RunStats;
+use crate::weapon::Arsenal;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PowerUp {
+    Speed,
+    FireRate,
+    Damage,
+    Shield,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PickupKind {
+    /// Heals this much.
+    Health(i32),
+    /// Refills this many rounds.
+    Ammo(u32),
+    PowerUp(PowerUp),
+    Experience(u32),
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct Pickup {
+    pub kind: PickupKind,
+    /// Seconds since it was dropped.
+    pub age: f32,
+}
+
+impl Pickup {
+    pub fn new(kind: PickupKind) -> Self {
+        Pickup { kind, age: 0.0 }
+    }
+}
+
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct PickupSettings {
+    /// Pickups closer than this to the player fly towards it.
+    pub magnet_radius: f32,
+    pub magnet_speed: f32,
+    /// Seconds a pickup lies around before disappearing.
+    pub lifetime: f32,
+    /// For how many of its last seconds a pickup blinks.
+    pub blink_time: f32,
+}
+
+impl Default for PickupSettings {
+    fn default() -> Self {
+        PickupSettings {
+            magnet_radius: 4.0,
+            magnet_speed: 12.0,
+            lifetime: 12.0,
+            blink_time: 3.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PickupCollected {
+    pub collector: Entity,
+    pub kind: PickupKind,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LootEntry {
+    pub kind: PickupKind,
+    /// Chance from 0 to 1 of this entry dropping, rolled on its own.
+    pub chance: f32,
+}
+
+/// What an enemy may drop when it dies. Every entry is rolled separately, so
+/// one death can drop several pickups.
+#[derive(Clone, Debug, Default)]
+pub struct LootTable {
+    pub entries: Vec<LootEntry>,
+}
+
+impl LootTable {
+    pub fn with(mut self, kind: PickupKind, chance: f32) -> Self {
+        self.entries.push(LootEntry { kind, chance });
+        self
+    }
+
+    /// The usual drops of a common enemy, plus `experience`.
+    pub fn standard(experience: u32) -> Self {
+        LootTable::default()
+            .with(PickupKind::Experience(experience), 1.0)
+            .with(PickupKind::Health(2), 0.08)
+            .with(PickupKind::Ammo(20), 0.12)
+            .with(PickupKind::PowerUp(PowerUp::Speed), 0.01)
+            .with(PickupKind::PowerUp(PowerUp::FireRate), 0.01)
+            .with(PickupKind::PowerUp(PowerUp::Damage), 0.01)
+            .with(PickupKind::PowerUp(PowerUp::Shield), 0.01)
+    }
+
+    pub fn roll<R: Rng>(&self, rng: &mut R) -> Vec<PickupKind> {
+        self.entries.iter()
+            .filter(|entry| rng.gen::<f32>() < entry.chance)
+            .map(|entry| entry.kind)
+            .collect()
+    }
+}
+
+#[derive(Resource)]
+pub struct PickupAssets {
+    pub mesh: Handle<Mesh>,
+    pub health_material: Handle<StandardMaterial>,
+    pub ammo_material: Handle<StandardMaterial>,
+    pub power_up_material: Handle<StandardMaterial>,
+    pub experience_material: Handle<StandardMaterial>,
+}
+
+impl PickupAssets {
+    pub fn material(&self, kind: PickupKind) -> Handle<StandardMaterial> {
+        match kind {
+            PickupKind::Health(_) => self.health_material.clone(),
+            PickupKind::Ammo(_) => self.ammo_material.clone(),
+            PickupKind::PowerUp(_) => self.power_up_material.clone(),
+            PickupKind::Experience(_) => self.experience_material.clone(),
+        }
+    }
+}
+
+fn create_pickup_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+){
+    let mut glowing = |color: Color| materials.add(StandardMaterial{
+        base_color: color,
+        emissive: color,
+        ..default()
+    });
+    commands.insert_resource(PickupAssets{
+        mesh: meshes.add(Mesh::from(shape::Cube{size: 0.4})),
+        health_material: glowing(Color::rgb(0.1, 1.0, 0.2)),
+        ammo_material: glowing(Color::rgb(1.0, 0.8, 0.1)),
+        power_up_material: glowing(Color::rgb(0.8, 0.2, 1.0)),
+        experience_material: glowing(Color::rgb(0.2, 0.6, 1.0)),
+    });
+}
+
+/// Drops a pickup on the floor at `position`.
+pub fn spawn_pickup(
+    commands: &mut Commands,
+    assets: &PickupAssets,
+    kind: PickupKind,
+    position: Vec3,
+) -> Entity {
+    commands
+        .spawn((
+            Pickup::new(kind),
+            StateScoped(AppState::InGame),
+            TriggerBundle::new(
+                TriggerKind::Pickup,
+                Collider::ball(0.5),
+                Transform::from_translation(Vec3::new(position.x, 0.5, position.z)),
+            ),
+            VisibilityBundle::default(),
+        ))
+        .with_children(|children| {
+            children.spawn(PbrBundle{
+                mesh: assets.mesh.clone(),
+                material: assets.material(kind),
+                transform: Transform::from_rotation(Quat::from_rotation_y(std::f32::consts::FRAC_PI_4)),
+                ..default()
+            });
+        })
+        .id()
+}
+
+fn attract_pickups(
+    mut pickups: Query<&mut Transform, With<Pickup>>,
+    player_info: Res<PlayerInfo>,
+    settings: Res<PickupSettings>,
+    time: Res<Time>,
+){
+    for mut transform in pickups.iter_mut(){
+        let to_player = Vec3::new(
+            player_info.position.x - transform.translation.x,
+            0.0,
+            player_info.position.z - transform.translation.z,
+        );
+        let distance = to_player.length();
+        if distance > settings.magnet_radius || distance <= f32::EPSILON{
+            continue;
+        }
+        // never step past the player
+        let step = (settings.magnet_speed * time.delta_seconds()).min(distance);
+        transform.translation += to_player / distance * step;
+    }
+}
+
+fn expire_pickups(
+    mut pickups: Query<(Entity, &mut Pickup, &mut Visibility)>,
+    settings: Res<PickupSettings>,
+    mut commands: Commands,
+    time: Res<Time>,
+){
+    for (entity, mut pickup, mut visibility) in pickups.iter_mut(){
+        pickup.age += time.delta_seconds();
+        let remaining = settings.lifetime - pickup.age;
+        if remaining <= 0.0{
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        if remaining < settings.blink_time{
+            // blink faster the closer it is to vanishing
+            let rate = 4.0 + 8.0 * (1.0 - remaining / settings.blink_time);
+            *visibility = if (pickup.age * rate).fract() < 0.5 { Visibility::Inherited } else { Visibility::Hidden };
+        }
+    }
+}
+
+fn collect_pickups(
+    mut trigger_events: EventReader<TriggerEnterEvent>,
+    pickups: Query<&Pickup>,
+    mut commands: Commands,
+    mut collected_events: EventWriter<PickupCollected>,
+){
+    let mut collected = Vec::new();
+    for trigger in trigger_events.iter(){
+        if trigger.kind != TriggerKind::Pickup || trigger.entrant.kind != CollidableKind::Player{
+            continue;
+        }
+        if collected.contains(&trigger.trigger.entity){
+            continue;
+        }
+        let Ok(pickup) = pickups.get(trigger.trigger.entity) else{
+            continue;
+        };
+        collected.push(trigger.trigger.entity);
+        commands.entity(trigger.trigger.entity).despawn_recursive();
+        collected_events.send(PickupCollected{collector: trigger.entrant.entity, kind: pickup.kind});
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn apply_pickups(
+    mut collected_events: EventReader<PickupCollected>,
+    mut collectors: Query<(Option<&mut Health>, Option<&mut Arsenal>, Option<&mut Buffs>)>,
+    rules: Res<BuffRules>,
+    mut stats: ResMut<RunStats>,
+    audio_controller: Res<AudioController>,
+    audio: Res<Audio>,
+){
+    for collected in collected_events.iter(){
+        let Ok((health, arsenal, buffs)) = collectors.get_mut(collected.collector) else{
+            continue;
+        };
+        match collected.kind {
+            PickupKind::Health(amount) => {
+                // the health bar follows `Changed<Health>`, so only touch it when it helps
+                if let Some(mut health) = health.filter(|health| health.current < health.max){
+                    health.heal(amount);
+                }
+            }
+            PickupKind::Ammo(amount) => {
+                if let Some(mut arsenal) = arsenal{
+                    arsenal.refill(amount);
+                }
+            }
+            PickupKind::PowerUp(power_up) => {
+                if let Some(mut buffs) = buffs{
+                    buffs.add(power_up, rules.get(power_up));
+                }
+            }
+            PickupKind::Experience(amount) => stats.experience += amount,
+        }
+        if let Some(sound) = audio_controller.get_handle("bonk"){
+            audio.play_with_settings(sound.handle, PlaybackSettings::ONCE.with_volume(0.2));
+        }
+    }
+}
+
+
+pub struct PickupPlugin;
+
+impl Plugin for PickupPlugin{
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PickupSettings>()
+        .add_event::<PickupCollected>()
+        .add_startup_system(create_pickup_assets)
+        .add_systems((
+            attract_pickups.in_set(OnUpdate(AppState::InGame)),
+            expire_pickups.in_set(OnUpdate(AppState::InGame)),
+            collect_pickups.in_set(OnUpdate(AppState::InGame)),
+            apply_pickups.in_set(OnUpdate(AppState::InGame)).after(collect_pickups),
+        ));
+    }
+}
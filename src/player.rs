@@ -1,35 +1,15 @@
 use bevy::{prelude::*, window::PrimaryWindow};
 use bevy_rapier3d::{prelude::*, rapier::crossbeam::channel::tick};
-use bevy::utils::Duration;
-use crate::{collision::*, audio::*, bullet::*, app_state::*, hud::HealthBar};
-
-#[derive(Component)]
-pub struct DamageCooldown{
-    pub timer: Timer,
-}
+use crate::{app_state::*, buff::Buffs, health::*, pickup::PowerUp};
 
 #[derive(Component)]
 pub struct Player {
     pub speed: f32,
-    pub health : i32,
 }
 
 #[derive(Resource, Clone)]
 pub struct PlayerMeshScene(pub Handle<Scene>);
 
-fn tick_damage_cooldown(
-    mut commands: Commands,
-    mut damage_cooldowns: Query<(Entity, &mut DamageCooldown)>,
-    time: Res<Time>
-){
-    for (entity, mut damage_cooldown) in damage_cooldowns.iter_mut(){
-        damage_cooldown.timer.tick(time.delta());
-        if damage_cooldown.timer.just_finished(){
-            damage_cooldown.timer.pause();
-        }
-    }
-}
-
 fn rotate_player(
     player_children: Query<&Children, With<Player>>,
     mut transforms: Query<&mut Transform, Without<Camera>>,
@@ -76,11 +56,12 @@ fn rotate_player(
 
 // Player Movement Input
 fn move_player(
-    mut player: Query<(&mut Velocity, &mut Player)>,
+    mut player: Query<(&mut Velocity, &mut Player, Option<&Buffs>)>,
     keyboard_input: Res<Input<KeyCode>>,
     timer: Res<Time>,
 ) {
-    for (mut vel, player) in player.iter_mut(){
+    for (mut vel, player, buffs) in player.iter_mut(){
+        let speed = player.speed * buffs.map_or(1.0, |buffs| buffs.multiplier(PowerUp::Speed));
         let movement_vec = Vec3::new(
             keyboard_input.pressed(KeyCode::A) as i32 as f32 * 1.0
                 + keyboard_input.pressed(KeyCode::D) as i32 as f32 * -1.0,
@@ -89,71 +70,22 @@ fn move_player(
                 + keyboard_input.pressed(KeyCode::S) as i32 as f32 * -1.0,
         );
         if movement_vec != Vec3::ZERO{
-            vel.linvel = movement_vec * timer.delta_seconds() * player.speed * Vec3::new(100.0,0.0,100.0);
+            vel.linvel = movement_vec * timer.delta_seconds() * speed * Vec3::new(100.0,0.0,100.0);
         }else{
             vel.linvel = Vec3::ZERO;
         }
     }
 }
 
-fn player_damage(
-    mut players: Query<(Entity, &mut DamageCooldown, &mut Player)>,
-    mut health_bar: Query<(&Node, &mut Style), With<HealthBar>>,
+pub fn player_death(
+    players: Query<Entity, With<Player>>,
+    mut death_events: EventReader<DeathEvent>,
     mut next_state: ResMut<NextState<AppState>>,
-    mut col_start_events: EventReader<CollisionStartEvent>,
-    mut col_stay_events: EventReader<CollisionStayEvent>,
 ){
-    let Ok(mut player) = players.get_single_mut() 
-    else{
-        // this really shouldn't happen lol.
-        warn!("MORE THAN ONE PLAYER");
-        return;
-    };
-    
-    // if the cool down is active ignore we don't want to check collisions.
-    if !player.1.timer.paused(){
-        return;
-    }
-
-    let Ok(mut hb) = health_bar.get_single_mut()
-    else{
-        return;
-    };
-    
-    for enter_event in col_start_events.iter(){
-        if enter_event.0.entity == player.0 || enter_event.1.entity == player.0 { 
-            if enter_event.0.kind != CollidableKind::Enemy && enter_event.1.kind != CollidableKind::Enemy{
-                continue;
-            }
-            //reset timer
-            player.1.timer.set_duration(Duration::from_secs(1));
-            player.1.timer.unpause();
-            //decrement health
-            player.2.health-=1;
-            hb.1.size = Size::new(Val::Percent(((player.2.health as f32) / 10.0 * 100.) as f32), Val::Percent(100.));
-
-            if player.2.health <= 0{
-                next_state.set(AppState::MainMenu);
-            }
-            return;
-        } 
-    }
-    for stay_event in col_stay_events.iter(){
-        if stay_event.0.entity == player.0 || stay_event.1.entity == player.0 {
-            if stay_event.0.kind != CollidableKind::Enemy && stay_event.1.kind != CollidableKind::Enemy{
-                continue;
-            }
-            // reset timer
-            player.1.timer.set_duration(Duration::from_secs(1));
-            player.1.timer.unpause();
-            //decrement health
-            player.2.health-=1;
-            hb.1.size = Size::new(Val::Percent(((player.2.health as f32) / 10.0 * 100.) as f32), Val::Percent(100.));
-            if player.2.health <= 0{
-                next_state.set(AppState::MainMenu);
-            }
-            return;
-        }  
+    for death in death_events.iter(){
+        if players.contains(death.entity){
+            next_state.set(AppState::GameOver);
+        }
     }
 }
 
@@ -186,41 +118,6 @@ fn update_player_info(
     }
 }
 
-// Player Shooting Input
-fn shoot_bullet(
-    player_info: Res<PlayerInfo>,
-    mouse_input: Res<Input<MouseButton>>,
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-) {
-    if mouse_input.just_pressed(MouseButton::Left) {
-        
-        println!("{}", player_info.forward);
-        let mut shooting_point = player_info.position.clone();
-        shooting_point.y -= 1.0;
-        shooting_point += 0.75*-player_info.forward;
-        commands
-            .spawn((
-                RigidBody::Dynamic,
-                Bullet{
-                    velocity: player_info.forward,
-                },
-                Collider::cuboid(0.05, 0.05, 0.25),
-                Collidable{kind: CollidableKind::Bullet},
-                Velocity::default(),
-                PbrBundle {
-                    mesh: meshes.add(Mesh::from(shape::Box::new(0.1, 0.1, 0.5))),
-                    material: materials.add(Color::rgb(0.0, 0.0, 1.0).into()),
-                    transform: Transform::from_translation(shooting_point).looking_at(player_info.forward+player_info.position, Vec3::Y),
-                    ..default()
-                },
-
-        ));
-    }
-}
-
-
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin{
@@ -230,9 +127,7 @@ impl Plugin for PlayerPlugin{
             rotate_player.in_set(OnUpdate(AppState::InGame)),
             move_player.in_set(OnUpdate(AppState::InGame)),
             update_player_info.in_set(OnUpdate(AppState::InGame)),
-            player_damage.in_set(OnUpdate(AppState::InGame)),
-            shoot_bullet.in_set(OnUpdate(AppState::InGame)),
-            tick_damage_cooldown.in_set(OnUpdate(AppState::InGame)),
+            player_death.in_set(OnUpdate(AppState::InGame)),
         ));
     }
 }
\ No newline at end of file
@@ -0,0 +1,122 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::app_state::{AppState, StateScoped};
+use crate::audio::*;
+use crate::enemy::{enemy_death, Enemy, EnemyRegistry};
+use crate::health::{apply_damage, DeathEvent};
+use crate::pickup::{spawn_pickup, PickupAssets};
+use crate::player::PlayerInfo;
+use crate::stats::{ComboSettings, RunStats};
+
+/// The flash left behind by a dying enemy.
+#[derive(Component)]
+pub struct DeathEffect {
+    pub timer: Timer,
+    pub size: f32,
+}
+
+#[derive(Resource)]
+pub struct DeathEffectAssets {
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<StandardMaterial>,
+}
+
+fn create_death_effect_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+){
+    commands.insert_resource(DeathEffectAssets{
+        mesh: meshes.add(Mesh::from(shape::UVSphere{radius: 0.5, ..default()})),
+        material: materials.add(StandardMaterial{
+            base_color: Color::rgba(1.0, 0.5, 0.1, 0.8),
+            emissive: Color::rgb(1.0, 0.5, 0.1),
+            alpha_mode: AlphaMode::Blend,
+            ..default()
+        }),
+    });
+}
+
+/// Pays out for every enemy killed. Runs off the same `DeathEvent` the enemy
+/// plugin despawns on, ordered between `apply_damage` and `enemy_death` so the
+/// event is read the frame it is sent, while the enemy is still around.
+#[allow(clippy::too_many_arguments)]
+fn enemy_rewards(
+    mut death_events: EventReader<DeathEvent>,
+    enemies: Query<&Enemy>,
+    registry: Res<EnemyRegistry>,
+    mut stats: ResMut<RunStats>,
+    combo_settings: Res<ComboSettings>,
+    pickup_assets: Res<PickupAssets>,
+    effect_assets: Res<DeathEffectAssets>,
+    player_info: Res<PlayerInfo>,
+    mut commands: Commands,
+    audio_controller: Res<AudioController>,
+    audio: Res<Audio>,
+){
+    let mut rng = rand::thread_rng();
+    for death in death_events.iter(){
+        let Ok(enemy) = enemies.get(death.entity) else{
+            continue;
+        };
+        stats.add_kill(enemy.kind, enemy.score, &combo_settings);
+
+        let size = registry.get(enemy.kind).map_or(1.0, |enemy_type| enemy_type.scale);
+        if let Some(enemy_type) = registry.get(enemy.kind){
+            for kind in enemy_type.loot.roll(&mut rng){
+                // scatter the drops so they don't sit on top of each other
+                let offset = Vec3::new(rng.gen_range(-0.5, 0.5), 0.0, rng.gen_range(-0.5, 0.5)) * size;
+                spawn_pickup(&mut commands, &pickup_assets, kind, death.position + offset);
+            }
+        }
+
+        commands.spawn((
+            DeathEffect{timer: Timer::from_seconds(0.4, TimerMode::Once), size},
+            StateScoped(AppState::InGame),
+            PbrBundle{
+                mesh: effect_assets.mesh.clone(),
+                material: effect_assets.material.clone(),
+                transform: Transform::from_translation(death.position).with_scale(Vec3::splat(size)),
+                ..default()
+            },
+        ));
+
+        if let Some(explosion) = audio_controller.get_handle("explosion"){
+            audio.play_spatial_with_settings(
+                explosion.handle, PlaybackSettings::ONCE.with_volume(0.5),
+                Transform::from_translation(player_info.position),
+                4.0,
+                death.position);
+        }
+    }
+}
+
+fn fade_death_effects(
+    mut effects: Query<(Entity, &mut DeathEffect, &mut Transform)>,
+    mut commands: Commands,
+    time: Res<Time>,
+){
+    for (entity, mut effect, mut transform) in effects.iter_mut(){
+        effect.timer.tick(time.delta());
+        // swells up then collapses
+        let t = effect.timer.percent();
+        transform.scale = Vec3::splat(effect.size * (1.0 + 2.0 * t) * (1.0 - t));
+        if effect.timer.finished(){
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+
+pub struct RewardsPlugin;
+
+impl Plugin for RewardsPlugin{
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(create_death_effect_assets)
+        .add_systems((
+            enemy_rewards.in_set(OnUpdate(AppState::InGame)).after(apply_damage).before(enemy_death),
+            fade_death_effects.in_set(OnUpdate(AppState::InGame)),
+        ));
+    }
+}
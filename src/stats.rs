@@ -0,0 +1,173 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::app_state::{starting_run, AppState};
+use crate::enemy::{Enemy, EnemyKind};
+use crate::health::{apply_damage, DamageEvent, DamageKind, DeathEvent, Health};
+use crate::player::{player_death, Player};
+use crate::weapon::ShotEvent;
+
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct ComboSettings {
+    /// Seconds after a kill before the combo runs out.
+    pub window: f32,
+    /// What every kill in the combo adds to the score multiplier.
+    pub step: f32,
+    pub max_multiplier: f32,
+}
+
+impl Default for ComboSettings {
+    fn default() -> Self {
+        ComboSettings { window: 3.0, step: 0.1, max_multiplier: 4.0 }
+    }
+}
+
+/// How the current run is going. Reset at the start of every run.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct RunStats {
+    pub score: u32,
+    pub experience: u32,
+    pub kills: HashMap<EnemyKind, u32>,
+    /// Counted per pellet.
+    pub shots_fired: u32,
+    pub shots_hit: u32,
+    pub damage_taken: i32,
+    /// Seconds spent in game, not counting pauses.
+    pub time_survived: f32,
+    /// Kills since the combo last broke.
+    pub combo: u32,
+    pub highest_combo: u32,
+    /// What killed the player, once it has died.
+    pub cause_of_death: Option<String>,
+    combo_timer: Timer,
+}
+
+impl RunStats {
+    pub fn total_kills(&self) -> u32 {
+        self.kills.values().sum()
+    }
+
+    /// Fraction of shots that hit something, 0 before the first shot.
+    pub fn accuracy(&self) -> f32 {
+        if self.shots_fired == 0{
+            return 0.0;
+        }
+        // a piercing shot can hit more than once
+        (self.shots_hit as f32 / self.shots_fired as f32).min(1.0)
+    }
+
+    pub fn multiplier(&self, settings: &ComboSettings) -> f32 {
+        (1.0 + self.combo as f32 * settings.step).min(settings.max_multiplier)
+    }
+
+    /// Counts a kill towards the combo and scores it. Returns the points awarded.
+    pub fn add_kill(&mut self, kind: EnemyKind, points: u32, settings: &ComboSettings) -> u32 {
+        *self.kills.entry(kind).or_insert(0) += 1;
+        let awarded = (points as f32 * self.multiplier(settings)).round() as u32;
+        self.score += awarded;
+        self.combo += 1;
+        self.highest_combo = self.highest_combo.max(self.combo);
+        self.combo_timer = Timer::from_seconds(settings.window, TimerMode::Once);
+        awarded
+    }
+
+    pub fn break_combo(&mut self) {
+        self.combo = 0;
+    }
+
+    /// Fraction of the combo window left, 0 with no combo going.
+    pub fn combo_remaining(&self) -> f32 {
+        if self.combo == 0{
+            return 0.0;
+        }
+        1.0 - self.combo_timer.percent()
+    }
+}
+
+fn reset_run_stats(
+    mut stats: ResMut<RunStats>,
+){
+    *stats = RunStats::default();
+}
+
+fn tick_run_stats(
+    mut stats: ResMut<RunStats>,
+    time: Res<Time>,
+){
+    stats.time_survived += time.delta_seconds();
+    if stats.combo > 0{
+        stats.combo_timer.tick(time.delta());
+        if stats.combo_timer.finished(){
+            stats.break_combo();
+        }
+    }
+}
+
+fn count_shots(
+    mut shot_events: EventReader<ShotEvent>,
+    mut damage_events: EventReader<DamageEvent>,
+    players: Query<(), With<Player>>,
+    enemies: Query<(), With<Enemy>>,
+    mut stats: ResMut<RunStats>,
+){
+    stats.shots_fired += shot_events.iter().filter(|shot| players.contains(shot.shooter)).count() as u32;
+    stats.shots_hit += damage_events.iter()
+        .filter(|damage| damage.kind == DamageKind::Projectile && enemies.contains(damage.target))
+        .filter(|damage| damage.source.is_some_and(|source| players.contains(source)))
+        .count() as u32;
+}
+
+/// Watches the player's health rather than damage events, so hits soaked up by
+/// a shield or damage cooldown don't count.
+fn track_damage_taken(
+    players: Query<(Entity, &Health), With<Player>>,
+    mut last_health: Local<Option<(Entity, i32)>>,
+    mut stats: ResMut<RunStats>,
+){
+    for (entity, health) in players.iter(){
+        if let Some((last_entity, last_current)) = *last_health{
+            if last_entity == entity && health.current < last_current{
+                stats.damage_taken += last_current - health.current;
+                stats.break_combo();
+            }
+        }
+        *last_health = Some((entity, health.current));
+    }
+}
+
+fn record_cause_of_death(
+    mut death_events: EventReader<DeathEvent>,
+    players: Query<(), With<Player>>,
+    enemies: Query<&Enemy>,
+    mut stats: ResMut<RunStats>,
+){
+    for death in death_events.iter(){
+        if !players.contains(death.entity){
+            continue;
+        }
+        let killer = death.source.and_then(|source| enemies.get(source).ok()).map(|enemy| enemy.kind);
+        stats.cause_of_death = Some(match (death.kind, killer) {
+            (DamageKind::Contact, Some(kind)) => format!("Caught by a {:?}", kind),
+            (DamageKind::Projectile, Some(kind)) => format!("Shot by a {:?}", kind),
+            (DamageKind::Hazard, _) => "Walked into a hazard".to_string(),
+            (_, None) => "Overwhelmed".to_string(),
+        });
+    }
+}
+
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin{
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunStats>()
+        .init_resource::<ComboSettings>()
+        .add_system(reset_run_stats.in_schedule(OnEnter(AppState::InGame)).run_if(starting_run))
+        .add_systems((
+            tick_run_stats.in_set(OnUpdate(AppState::InGame)),
+            count_shots.in_set(OnUpdate(AppState::InGame)),
+            track_damage_taken.in_set(OnUpdate(AppState::InGame)),
+            record_cause_of_death.in_set(OnUpdate(AppState::InGame)).after(apply_damage).before(player_death),
+        ));
+    }
+}
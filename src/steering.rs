@@ -0,0 +1,187 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use rand::Rng;
+
+use crate::app_state::AppState;
+use crate::collision::CollidableKind;
+use crate::navigation::NavAgent;
+use crate::player::PlayerInfo;
+
+/// How much each behaviour pulls on an agent. Behaviours with a zero weight
+/// are skipped, so an agent only pays for what it uses.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SteeringWeights {
+    /// Head straight for the player.
+    pub seek: f32,
+    /// Run straight away from the player.
+    pub flee: f32,
+    /// Keep clear of other agents within `separation_radius`.
+    pub separation: f32,
+    /// Turn away from walls found by rays `lookahead` long.
+    pub wall_avoidance: f32,
+    /// Drift around randomly.
+    pub wander: f32,
+    /// Circle the player at `orbit_range`.
+    pub orbit: f32,
+    pub orbit_range: f32,
+    pub separation_radius: f32,
+    pub lookahead: f32,
+}
+
+impl Default for SteeringWeights {
+    fn default() -> Self {
+        SteeringWeights {
+            seek: 1.0,
+            flee: 0.0,
+            separation: 1.5,
+            wall_avoidance: 2.0,
+            wander: 0.2,
+            orbit: 0.0,
+            orbit_range: 8.0,
+            separation_radius: 2.0,
+            lookahead: 2.0,
+        }
+    }
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct Steering {
+    pub weights: SteeringWeights,
+    pub max_speed: f32,
+    /// Where on its wander circle the agent is heading, in radians.
+    pub wander_angle: f32,
+}
+
+impl Steering {
+    pub fn new(weights: SteeringWeights, max_speed: f32) -> Self {
+        Steering { weights, max_speed, wander_angle: 0.0 }
+    }
+}
+
+fn flat(vector: Vec3) -> Vec3 {
+    Vec3::new(vector.x, 0.0, vector.z)
+}
+
+pub fn seek(position: Vec3, target: Vec3) -> Vec3 {
+    flat(target - position).normalize_or_zero()
+}
+
+pub fn flee(position: Vec3, target: Vec3) -> Vec3 {
+    -seek(position, target)
+}
+
+/// Pushes away from every neighbour closer than `radius`, harder the closer it is.
+pub fn separation(position: Vec3, neighbours: impl Iterator<Item = Vec3>, radius: f32) -> Vec3 {
+    let mut push = Vec3::ZERO;
+    for neighbour in neighbours{
+        let away = flat(position - neighbour);
+        let distance = away.length();
+        if distance > 0.0 && distance < radius{
+            push += away / distance * (1.0 - distance / radius);
+        }
+    }
+    push
+}
+
+/// Circles `target` while pulling back towards `range` from it.
+pub fn orbit(position: Vec3, target: Vec3, range: f32) -> Vec3 {
+    let to_target = flat(target - position);
+    let distance = to_target.length();
+    if distance <= f32::EPSILON{
+        return Vec3::ZERO;
+    }
+    let towards = to_target / distance;
+    let tangent = Vec3::new(-towards.z, 0.0, towards.x);
+    // inside the range the radial term turns negative and backs away
+    let radial = towards * ((distance - range) / range).clamp(-1.0, 1.0);
+    tangent + radial
+}
+
+/// Heads towards a point on a circle in front of the agent that jitters a
+/// little every frame, giving a smooth random walk.
+pub fn wander<R: Rng>(heading: Vec3, wander_angle: &mut f32, rng: &mut R) -> Vec3 {
+    *wander_angle += rng.gen_range(-0.3, 0.3);
+    let heading = flat(heading).normalize_or_zero();
+    let offset = Vec3::new(wander_angle.cos(), 0.0, wander_angle.sin());
+    (heading * 2.0 + offset).normalize_or_zero()
+}
+
+/// Casts a fan of rays along `heading` and steers away from any wall they hit.
+fn wall_avoidance(context: &RapierContext, entity: Entity, position: Vec3, heading: Vec3, lookahead: f32) -> Vec3 {
+    let heading = flat(heading).normalize_or_zero();
+    if heading == Vec3::ZERO{
+        return Vec3::ZERO;
+    }
+    let filter = QueryFilter::from(CollisionGroups::new(Group::ALL, CollidableKind::Wall.group()))
+        .exclude_rigid_body(entity);
+    let mut push = Vec3::ZERO;
+    for angle in [-0.5_f32, 0.0, 0.5]{
+        let direction = Quat::from_axis_angle(Vec3::Y, angle) * heading;
+        if let Some((_, hit)) = context.cast_ray_and_get_normal(position, direction, lookahead, true, filter){
+            push += flat(hit.normal) * (1.0 - hit.toi / lookahead);
+        }
+    }
+    push
+}
+
+pub fn apply_steering(
+    mut agents: Query<(Entity, &mut Steering, &mut Transform, &mut Velocity, Option<&NavAgent>)>,
+    player_info: Res<PlayerInfo>,
+    context: Res<RapierContext>,
+){
+    let mut rng = rand::thread_rng();
+    let positions: Vec<(Entity, Vec3)> = agents.iter()
+        .map(|(entity, _, transform, _, _)| (entity, transform.translation))
+        .collect();
+
+    for (entity, mut steering, mut transform, mut velocity, agent) in agents.iter_mut(){
+        let weights = steering.weights;
+        let position = transform.translation;
+        // agents with a path around obstacles seek its next waypoint instead
+        let seek_target = agent.and_then(NavAgent::next_waypoint).unwrap_or(player_info.position);
+        let heading = if velocity.linvel.length_squared() > f32::EPSILON {
+            velocity.linvel
+        } else {
+            seek(position, seek_target)
+        };
+
+        let mut desired = Vec3::ZERO;
+        if weights.seek != 0.0{
+            desired += seek(position, seek_target) * weights.seek;
+        }
+        if weights.flee != 0.0{
+            desired += flee(position, player_info.position) * weights.flee;
+        }
+        if weights.separation != 0.0{
+            let neighbours = positions.iter()
+                .filter(|(other, _)| *other != entity)
+                .map(|(_, other_position)| *other_position);
+            desired += separation(position, neighbours, weights.separation_radius) * weights.separation;
+        }
+        if weights.orbit != 0.0{
+            desired += orbit(position, player_info.position, weights.orbit_range) * weights.orbit;
+        }
+        if weights.wander != 0.0{
+            desired += wander(heading, &mut steering.wander_angle, &mut rng) * weights.wander;
+        }
+        if weights.wall_avoidance != 0.0{
+            desired += wall_avoidance(&context, entity, position, heading, weights.lookahead) * weights.wall_avoidance;
+        }
+
+        let direction = flat(desired).normalize_or_zero();
+        velocity.linvel = direction * steering.max_speed;
+        if direction != Vec3::ZERO{
+            // the models face along -forward
+            transform.rotation = Quat::from_axis_angle(Vec3::Y, direction.x.atan2(direction.z));
+        }
+    }
+}
+
+
+pub struct SteeringPlugin;
+
+impl Plugin for SteeringPlugin{
+    fn build(&self, app: &mut App) {
+        app.add_system(apply_steering.in_set(OnUpdate(AppState::InGame)));
+    }
+}
@@ -0,0 +1,560 @@
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use bevy_rapier3d::prelude::*;
+use rand::Rng;
+
+use crate::app_state::{AppState, StateScoped};
+use crate::audio::*;
+use crate::bullet::{Bullet, Faction, ProjectilePool};
+use crate::collision::*;
+use crate::health::*;
+use crate::buff::Buffs;
+use crate::pickup::PowerUp;
+use crate::player::{Player, PlayerInfo};
+
+/// Slowest a weapon may fire, in shots per second.
+pub const MIN_FIRE_RATE: f32 = 0.1;
+pub const MIN_PROJECTILE_SPEED: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FireMode {
+    /// One shot per click.
+    SemiAuto,
+    /// Keeps firing at `fire_rate` while the button is held.
+    FullAuto,
+    /// One click fires `shots` shots spaced `interval` seconds apart.
+    Burst { shots: u32, interval: f32 },
+    /// Hold for `time` seconds, releasing a full charge fires.
+    Charge { time: f32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Delivery {
+    /// Fires pooled bullets that travel at `projectile_speed`.
+    Projectile,
+    /// Hits instantly along a ray. The ray passes through up to `pierce`
+    /// enemies and bounces off up to `ricochets` walls.
+    Hitscan { pierce: u32, ricochets: u32 },
+}
+
+/// Everything that makes one gun different from another. `Weapon::new` is
+/// the starting pistol, see `Arsenal::default` for how the others differ.
+#[derive(Clone, Debug)]
+pub struct Weapon {
+    pub name: String,
+    /// Shots per second.
+    pub fire_rate: f32,
+    pub projectile_speed: f32,
+    /// How far a projectile flies before it expires.
+    pub range: f32,
+    /// Total width of the cone pellets are scattered in, in radians.
+    pub spread: f32,
+    pub pellets: u32,
+    pub damage: i32,
+    /// Name of the sound in the `AudioController`.
+    pub sound: String,
+    pub mode: FireMode,
+    pub delivery: Delivery,
+    /// Rounds left, `None` for a weapon that never runs dry.
+    pub ammo: Option<u32>,
+    pub max_ammo: Option<u32>,
+}
+
+impl Weapon {
+    pub fn new(name: &str) -> Self {
+        Weapon {
+            name: name.to_string(),
+            fire_rate: 4.0,
+            projectile_speed: 10.0,
+            range: 30.0,
+            spread: 0.0,
+            pellets: 1,
+            damage: 1,
+            sound: "gunshot".to_string(),
+            mode: FireMode::SemiAuto,
+            delivery: Delivery::Projectile,
+            ammo: None,
+            max_ammo: None,
+        }
+    }
+
+    /// Clamped to `MIN_FIRE_RATE`, the cooldown is its inverse.
+    pub fn fire_rate(mut self, fire_rate: f32) -> Self {
+        self.fire_rate = fire_rate.max(MIN_FIRE_RATE);
+        self
+    }
+
+    /// Clamped to `MIN_PROJECTILE_SPEED`, the bullet lifetime divides by it.
+    pub fn projectile_speed(mut self, projectile_speed: f32) -> Self {
+        self.projectile_speed = projectile_speed.max(MIN_PROJECTILE_SPEED);
+        self
+    }
+
+    pub fn range(mut self, range: f32) -> Self {
+        self.range = range;
+        self
+    }
+
+    pub fn spread(mut self, spread: f32) -> Self {
+        self.spread = spread;
+        self
+    }
+
+    pub fn pellets(mut self, pellets: u32) -> Self {
+        self.pellets = pellets;
+        self
+    }
+
+    pub fn damage(mut self, damage: i32) -> Self {
+        self.damage = damage;
+        self
+    }
+
+    pub fn sound(mut self, sound: &str) -> Self {
+        self.sound = sound.to_string();
+        self
+    }
+
+    pub fn mode(mut self, mode: FireMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn hitscan(mut self, pierce: u32, ricochets: u32) -> Self {
+        self.delivery = Delivery::Hitscan { pierce, ricochets };
+        self
+    }
+
+    /// Gives the weapon a magazine of `max_ammo` rounds, starting full.
+    pub fn ammo(mut self, max_ammo: u32) -> Self {
+        self.ammo = Some(max_ammo);
+        self.max_ammo = Some(max_ammo);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ammo == Some(0)
+    }
+
+    /// A copy of the weapon with the holder's power ups applied.
+    pub fn buffed(&self, buffs: &Buffs) -> Weapon {
+        let mut weapon = self.clone();
+        weapon.fire_rate *= buffs.multiplier(PowerUp::FireRate);
+        weapon.damage = (weapon.damage as f32 * buffs.multiplier(PowerUp::Damage)).round() as i32;
+        weapon
+    }
+
+    fn cooldown(&self) -> Duration {
+        Duration::from_secs_f32(1.0 / self.fire_rate)
+    }
+}
+
+/// The weapons a player is carrying and which one is in hand.
+#[derive(Component, Clone, Debug)]
+pub struct Arsenal {
+    pub weapons: Vec<Weapon>,
+    pub current: usize,
+}
+
+impl Arsenal {
+    pub fn current(&self) -> &Weapon {
+        &self.weapons[self.current]
+    }
+
+    pub fn current_mut(&mut self) -> &mut Weapon {
+        &mut self.weapons[self.current]
+    }
+
+    /// Adds `amount` rounds to every weapon that uses ammo.
+    pub fn refill(&mut self, amount: u32) {
+        for weapon in self.weapons.iter_mut(){
+            if let (Some(ammo), Some(max_ammo)) = (weapon.ammo.as_mut(), weapon.max_ammo){
+                *ammo = (*ammo + amount).min(max_ammo);
+            }
+        }
+    }
+
+    pub fn cycle(&mut self, step: isize) {
+        let len = self.weapons.len() as isize;
+        self.current = (self.current as isize + step).rem_euclid(len) as usize;
+    }
+}
+
+impl Default for Arsenal {
+    fn default() -> Self {
+        Arsenal {
+            weapons: vec![
+                Weapon::new("pistol"),
+                Weapon::new("rifle")
+                    .ammo(120)
+                    .mode(FireMode::FullAuto)
+                    .fire_rate(8.0)
+                    .projectile_speed(14.0)
+                    .spread(0.08)
+                    .sound("laser"),
+                Weapon::new("shotgun")
+                    .ammo(24)
+                    .fire_rate(1.2)
+                    .projectile_speed(12.0)
+                    .pellets(6)
+                    .spread(0.5)
+                    .range(12.0)
+                    .sound("bonk"),
+                Weapon::new("burst")
+                    .ammo(60)
+                    .mode(FireMode::Burst { shots: 3, interval: 0.08 })
+                    .fire_rate(2.0)
+                    .projectile_speed(14.0)
+                    .sound("laser"),
+                Weapon::new("railgun")
+                    .ammo(10)
+                    .mode(FireMode::Charge { time: 1.0 })
+                    .projectile_speed(25.0)
+                    .range(60.0)
+                    .damage(5)
+                    .sound("inferno"),
+                Weapon::new("lance")
+                    .ammo(20)
+                    .hitscan(2, 0)
+                    .fire_rate(1.5)
+                    .range(40.0)
+                    .damage(2)
+                    .sound("laser"),
+                Weapon::new("bouncer")
+                    .ammo(30)
+                    .hitscan(0, 3)
+                    .fire_rate(2.0)
+                    .range(60.0)
+                    .sound("laser"),
+            ],
+            current: 0,
+        }
+    }
+}
+
+/// Per-player firing state for the weapon in hand, reset on every swap.
+#[derive(Component)]
+pub struct WeaponState {
+    pub cooldown: Timer,
+    pub burst_remaining: u32,
+    pub burst_timer: Timer,
+    pub charge: f32,
+}
+
+impl Default for WeaponState {
+    fn default() -> Self {
+        WeaponState {
+            cooldown: Timer::new(Duration::ZERO, TimerMode::Once),
+            burst_remaining: 0,
+            burst_timer: Timer::new(Duration::ZERO, TimerMode::Repeating),
+            charge: 0.0,
+        }
+    }
+}
+
+fn cycle_weapon(
+    mut players: Query<(&mut Arsenal, &mut WeaponState), With<Player>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut scroll_events: EventReader<bevy::input::mouse::MouseWheel>,
+){
+    let mut step: isize = keyboard_input.just_pressed(KeyCode::E) as isize - keyboard_input.just_pressed(KeyCode::Q) as isize;
+    for scroll in scroll_events.iter(){
+        step += scroll.y.signum() as isize;
+    }
+    let number_keys = [
+        KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4, KeyCode::Key5,
+        KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9,
+    ];
+
+    for (mut arsenal, mut state) in players.iter_mut(){
+        let previous = arsenal.current;
+        if let Some(slot) = number_keys.iter().position(|key| keyboard_input.just_pressed(*key)){
+            if slot < arsenal.weapons.len(){
+                arsenal.current = slot;
+            }
+        }else if step != 0{
+            arsenal.cycle(step);
+        }
+        if arsenal.current != previous{
+            *state = WeaponState::default();
+        }
+    }
+}
+
+/// Works out how many shots the weapon in hand fires this frame.
+fn pull_trigger(weapon: &Weapon, state: &mut WeaponState, mouse_input: &Input<MouseButton>, delta: Duration) -> u32 {
+    state.cooldown.tick(delta);
+    let ready = state.cooldown.finished();
+    let mut shots = 0;
+
+    match weapon.mode {
+        FireMode::SemiAuto => {
+            if ready && mouse_input.just_pressed(MouseButton::Left){
+                shots = 1;
+            }
+        }
+        FireMode::FullAuto => {
+            if ready && mouse_input.pressed(MouseButton::Left){
+                shots = 1;
+            }
+        }
+        FireMode::Burst { shots: burst, interval } => {
+            if state.burst_remaining > 0{
+                state.burst_timer.tick(delta);
+                if state.burst_timer.just_finished(){
+                    shots = 1;
+                    state.burst_remaining -= 1;
+                }
+            }else if ready && mouse_input.just_pressed(MouseButton::Left){
+                shots = 1;
+                state.burst_remaining = burst.saturating_sub(1);
+                state.burst_timer = Timer::from_seconds(interval, TimerMode::Repeating);
+            }
+        }
+        FireMode::Charge { time } => {
+            if ready && mouse_input.pressed(MouseButton::Left){
+                state.charge = (state.charge + delta.as_secs_f32() / time).min(1.0);
+            }
+            if mouse_input.just_released(MouseButton::Left){
+                if state.charge >= 1.0{
+                    shots = 1;
+                }
+                state.charge = 0.0;
+            }
+        }
+    }
+
+    // the cooldown starts from the first shot of a burst
+    if shots > 0 && ready{
+        state.cooldown = Timer::new(weapon.cooldown(), TimerMode::Once);
+    }
+    shots
+}
+
+/// One pellet leaving a weapon. Whoever handles the weapon's `Delivery`
+/// turns it into a bullet or a ray.
+#[derive(Clone, Debug)]
+pub struct ShotEvent {
+    pub shooter: Entity,
+    pub origin: Vec3,
+    pub direction: Vec3,
+    pub weapon: Weapon,
+}
+
+/// The beam left behind by a hitscan shot.
+#[derive(Component)]
+pub struct Tracer {
+    pub timer: Timer,
+}
+
+#[derive(Resource)]
+pub struct TracerAssets {
+    /// A unit long beam along -Z, scaled to length when spawned.
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<StandardMaterial>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn fire_weapon(
+    mut players: Query<(Entity, &mut Arsenal, &mut WeaponState, Option<&Buffs>), With<Player>>,
+    player_info: Res<PlayerInfo>,
+    mouse_input: Res<Input<MouseButton>>,
+    time: Res<Time>,
+    mut shot_events: EventWriter<ShotEvent>,
+    audio_controller: Res<AudioController>,
+    audio: Res<Audio>,
+){
+    let mut rng = rand::thread_rng();
+    for (entity, mut arsenal, mut state, buffs) in players.iter_mut(){
+        if arsenal.current().is_empty(){
+            *state = WeaponState::default();
+            continue;
+        }
+        let weapon = match buffs {
+            Some(buffs) => arsenal.current().buffed(buffs),
+            None => arsenal.current().clone(),
+        };
+        let mut shots = pull_trigger(&weapon, &mut state, &mouse_input, time.delta());
+        if shots == 0{
+            continue;
+        }
+        if let Some(ammo) = arsenal.current_mut().ammo.as_mut(){
+            shots = shots.min(*ammo);
+            *ammo -= shots;
+        }
+
+        // the player model faces away from its transform's forward
+        let aim = -player_info.forward;
+        let mut shooting_point = player_info.position;
+        shooting_point.y -= 1.0;
+        shooting_point += 0.75 * aim;
+
+        for _ in 0..shots * weapon.pellets{
+            let offset = if weapon.spread > 0.0 {
+                rng.gen_range(-weapon.spread / 2.0, weapon.spread / 2.0)
+            } else {
+                0.0
+            };
+            shot_events.send(ShotEvent{
+                shooter: entity,
+                origin: shooting_point,
+                direction: Quat::from_axis_angle(Vec3::Y, offset) * aim,
+                weapon: weapon.clone(),
+            });
+        }
+
+        if let Some(sound) = audio_controller.get_handle(&weapon.sound){
+            audio.play_with_settings(sound.handle, PlaybackSettings::ONCE.with_volume(0.3));
+        }
+    }
+}
+
+fn spawn_projectiles(
+    mut shot_events: EventReader<ShotEvent>,
+    mut commands: Commands,
+    mut pool: ResMut<ProjectilePool>,
+){
+    for shot in shot_events.iter(){
+        if shot.weapon.delivery != Delivery::Projectile{
+            continue;
+        }
+        let weapon = &shot.weapon;
+        pool.spawn(
+            &mut commands,
+            shot.origin,
+            shot.direction * weapon.projectile_speed,
+            // generous lifetime so range is what normally expires it
+            Bullet::new(weapon.damage, 2.0 * weapon.range / weapon.projectile_speed, weapon.range)
+                .fired_by(shot.shooter, Faction::Player),
+        );
+    }
+}
+
+fn create_tracer_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+){
+    commands.insert_resource(TracerAssets{
+        mesh: meshes.add(Mesh::from(shape::Box::new(0.05, 0.05, 1.0))),
+        material: materials.add(StandardMaterial{
+            base_color: Color::rgb(1.0, 1.0, 0.3),
+            emissive: Color::rgb(1.0, 1.0, 0.3),
+            unlit: true,
+            ..default()
+        }),
+    });
+}
+
+fn trace_hitscan(
+    mut shot_events: EventReader<ShotEvent>,
+    collidables: Query<&Collidable>,
+    context: Res<RapierContext>,
+    matrix: Res<CollisionMatrix>,
+    tracer_assets: Res<TracerAssets>,
+    mut commands: Commands,
+    mut damage_events: EventWriter<DamageEvent>,
+){
+    for shot in shot_events.iter(){
+        let Delivery::Hitscan { pierce, ricochets } = shot.weapon.delivery else{
+            continue;
+        };
+        let mut pierce_left = pierce;
+        let mut ricochets_left = ricochets;
+        let mut range_left = shot.weapon.range;
+        let mut direction = shot.direction.normalize_or_zero();
+        // cast at enemy height rather than from the muzzle at floor level
+        let mut origin = Vec3::new(shot.origin.x, 0.5, shot.origin.z);
+        let mut already_hit: Vec<Entity> = Vec::new();
+
+        while range_left > 0.0{
+            let skip_hit = |entity: Entity| !already_hit.contains(&entity);
+            // rays collide with whatever a bullet would
+            let filter = QueryFilter::from(matrix.collision_groups(CollidableKind::Bullet))
+                .exclude_rigid_body(shot.shooter)
+                .predicate(&skip_hit);
+            let Some((entity, intersection)) = context.cast_ray_and_get_normal(origin, direction, range_left, true, filter) else{
+                spawn_tracer(&mut commands, &tracer_assets, origin, origin + direction * range_left);
+                break;
+            };
+            spawn_tracer(&mut commands, &tracer_assets, origin, intersection.point);
+            range_left -= intersection.toi;
+
+            match collidables.get(entity).map(|collidable| collidable.kind) {
+                Ok(CollidableKind::Enemy) => {
+                    damage_events.send(DamageEvent{
+                        target: entity,
+                        source: Some(shot.shooter),
+                        amount: shot.weapon.damage,
+                        kind: DamageKind::Projectile,
+                    });
+                    if pierce_left == 0{
+                        break;
+                    }
+                    pierce_left -= 1;
+                    already_hit.push(entity);
+                    origin = intersection.point;
+                }
+                Ok(CollidableKind::Wall) if ricochets_left > 0 => {
+                    ricochets_left -= 1;
+                    direction = direction - 2.0 * direction.dot(intersection.normal) * intersection.normal;
+                    // step off the wall so the next cast doesn't hit it again
+                    origin = intersection.point + intersection.normal * 0.01;
+                }
+                _ => break,
+            }
+        }
+    }
+}
+
+fn spawn_tracer(commands: &mut Commands, tracer_assets: &TracerAssets, from: Vec3, to: Vec3){
+    let length = from.distance(to);
+    if length <= f32::EPSILON{
+        return;
+    }
+    commands.spawn((
+        Tracer{timer: Timer::from_seconds(0.1, TimerMode::Once)},
+        StateScoped(AppState::InGame),
+        PbrBundle {
+            mesh: tracer_assets.mesh.clone(),
+            material: tracer_assets.material.clone(),
+            transform: Transform::from_translation((from + to) / 2.0)
+                .looking_at(to, Vec3::Y)
+                .with_scale(Vec3::new(1.0, 1.0, length)),
+            ..default()
+        },
+    ));
+}
+
+fn fade_tracers(
+    mut tracers: Query<(Entity, &mut Tracer, &mut Transform)>,
+    mut commands: Commands,
+    time: Res<Time>,
+){
+    for (entity, mut tracer, mut transform) in tracers.iter_mut(){
+        tracer.timer.tick(time.delta());
+        let width = 1.0 - tracer.timer.percent();
+        transform.scale.x = width;
+        transform.scale.y = width;
+        if tracer.timer.finished(){
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+
+pub struct WeaponPlugin;
+
+impl Plugin for WeaponPlugin{
+    fn build(&self, app: &mut App) {
+        app.add_event::<ShotEvent>()
+        .add_startup_system(create_tracer_assets)
+        .add_systems((
+            cycle_weapon.in_set(OnUpdate(AppState::InGame)),
+            fire_weapon.in_set(OnUpdate(AppState::InGame)).after(cycle_weapon),
+            spawn_projectiles.in_set(OnUpdate(AppState::InGame)).after(fire_weapon),
+            trace_hitscan.in_set(OnUpdate(AppState::InGame)).after(fire_weapon),
+            fade_tracers.in_set(OnUpdate(AppState::InGame)),
+        ));
+    }
+}